@@ -0,0 +1,196 @@
+use crate::model::Grades;
+use crate::rubric::Criterion;
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+
+// what changed a grade or comment
+pub enum Source {
+    // grader run on commit
+    Grader(Option<String>),
+    Manual,
+    Repeat,
+    Rubric,
+    Snippet,
+    Command,
+    // loaded from a file changed outside, e.g. results or merged files
+    Import(String),
+    Undo,
+    Redo,
+}
+
+impl Source {
+    pub fn name(&self) -> String {
+        match self {
+            Source::Grader(Some(commit)) => format!("grader {}", commit),
+            Source::Grader(None) => "grader".to_string(),
+            Source::Manual => "manual".to_string(),
+            Source::Repeat => "repeat".to_string(),
+            Source::Rubric => "rubric".to_string(),
+            Source::Snippet => "snippet".to_string(),
+            Source::Command => "command".to_string(),
+            Source::Import(path) => format!("import {}", path),
+            Source::Undo => "undo".to_string(),
+            Source::Redo => "redo".to_string(),
+        }
+    }
+}
+
+// append-only log next to results
+pub fn path(results: &str) -> String {
+    format!("{}.audit.csv", results)
+}
+
+fn user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn grade(grade: Option<f64>) -> String {
+    grade.map_or("N/A".to_string(), |grade| grade.to_string())
+}
+
+// changed fields of a student: field, old value, new value
+pub fn changes(before: &Grades, after: &Grades, criteria: &[Criterion]) -> Vec<[String; 3]> {
+    let mut changes = Vec::new();
+    if before.blackbox != after.blackbox {
+        changes.push([
+            "blackbox".to_string(),
+            grade(before.blackbox),
+            grade(after.blackbox),
+        ]);
+    }
+    if before.whitebox != after.whitebox {
+        changes.push([
+            "whitebox".to_string(),
+            grade(before.whitebox),
+            grade(after.whitebox),
+        ]);
+    }
+    if before.comment != after.comment {
+        changes.push([
+            "comment".to_string(),
+            before.comment.clone().unwrap_or_default(),
+            after.comment.clone().unwrap_or_default(),
+        ]);
+    }
+    if before.deduction != after.deduction {
+        changes.push([
+            "deduction".to_string(),
+            before.deduction.to_string(),
+            after.deduction.to_string(),
+        ]);
+    }
+    for (index, criterion) in criteria.iter().enumerate() {
+        let old = before.rubric.get(index).cloned().flatten();
+        let new = after.rubric.get(index).cloned().flatten();
+        if old != new {
+            changes.push([criterion.name.clone(), grade(old), grade(new)]);
+        }
+    }
+    changes
+}
+
+// values of a student not matching the last change in log, as changes from it
+pub fn imported(
+    student_id: &str,
+    grades: &Grades,
+    criteria: &[Criterion],
+    last: &HashMap<(String, String), (DateTime<Utc>, String)>,
+) -> Vec<[String; 3]> {
+    changes(&Grades::default(), grades, criteria)
+        .into_iter()
+        .filter_map(
+            |[field, old, new]| match last.get(&(student_id.to_string(), field.clone())) {
+                Some((_, value)) if *value == new => None,
+                Some((_, value)) => Some([field, value.clone(), new]),
+                // e.g. an empty comment
+                None if old == new => None,
+                None => Some([field, old, new]),
+            },
+        )
+        .collect()
+}
+
+// append changes of a student
+pub fn log(
+    path: &str,
+    student_id: &str,
+    changes: &[[String; 3]],
+    source: &Source,
+) -> csv::Result<()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let exists = Path::new(path).exists();
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut wtr = csv::Writer::from_writer(file);
+    if !exists {
+        wtr.write_record([
+            "time",
+            "user",
+            "student_id",
+            "field",
+            "old",
+            "new",
+            "source",
+        ])?;
+    }
+    let time = Local::now().to_rfc3339_opts(SecondsFormat::Secs, false);
+    let user = user();
+    let source = source.name();
+    for [field, old, new] in changes.iter() {
+        wtr.write_record([&time, &user, student_id, field, old, new, &source])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// changes of a student, oldest first, without student id
+pub fn history(path: &str, student_id: &str) -> Vec<Vec<String>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Vec::new(),
+    };
+    let mut rdr = csv::Reader::from_reader(file);
+    rdr.records()
+        .filter_map(Result::ok)
+        .filter(|record| record.get(2) == Some(student_id))
+        .map(|record| {
+            record
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != 2)
+                .map(|(_, value)| value.to_string())
+                .collect()
+        })
+        .collect()
+}
+
+// time and new value of last change of each student id and field
+pub fn last_changes(path: &str) -> HashMap<(String, String), (DateTime<Utc>, String)> {
+    let mut changes = HashMap::new();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return changes,
+    };
+    let mut rdr = csv::Reader::from_reader(file);
+    for record in rdr.records().filter_map(Result::ok) {
+        let time = match record.get(0).map(DateTime::parse_from_rfc3339) {
+            Some(Ok(time)) => time.with_timezone(&Utc),
+            _ => continue,
+        };
+        let key = (
+            record.get(2).unwrap_or("").to_string(),
+            record.get(3).unwrap_or("").to_string(),
+        );
+        let value = record.get(5).unwrap_or("").to_string();
+        let last = changes.entry(key).or_insert((time, value.clone()));
+        if time >= last.0 {
+            *last = (time, value);
+        }
+    }
+    changes
+}
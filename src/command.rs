@@ -0,0 +1,285 @@
+// commands typed after ":" in tui
+
+pub enum Selector {
+    // every student, ignoring filter and search
+    All,
+    Selected,
+    Ungraded,
+    Late,
+    // fetch or grader failed
+    Failed,
+    Attention,
+    Commented,
+    // roster column or team equals value
+    Column(String, String),
+}
+
+pub enum Format {
+    Csv,
+    Json,
+}
+
+pub enum Command {
+    Grade(Vec<Selector>),
+    Fetch(Vec<Selector>),
+    // true for whitebox, false for blackbox
+    Set(bool, Option<f64>, Vec<Selector>),
+    Export(Format, String),
+    // branch of student repos to fetch
+    Branch(String),
+    Save,
+    Help,
+    Quit,
+}
+
+const COMMANDS: [&str; 8] = [
+    "grade", "fetch", "set", "export", "branch", "save", "help", "quit",
+];
+
+const SELECTORS: [&str; 7] = [
+    "all",
+    "selected",
+    "ungraded",
+    "late",
+    "failed",
+    "attention",
+    "commented",
+];
+
+pub const USAGE: [&str; 8] = [
+    "grade [selector...]: grade blackbox of selected students",
+    "fetch [selector...]: fetch repos of selected students",
+    "set blackbox|whitebox <grade|none> [selector...]: set grade of selected students",
+    "export csv|json <file>: export results to file",
+    "branch <name>: fetch this branch of student repos",
+    "save: save results",
+    "help: show this usage",
+    "quit: quit",
+];
+
+fn parse_selectors(words: &[&str]) -> Result<Vec<Selector>, String> {
+    let mut selectors = Vec::new();
+    for word in words.iter() {
+        selectors.push(match *word {
+            "all" => Selector::All,
+            "selected" => Selector::Selected,
+            "ungraded" => Selector::Ungraded,
+            "late" => Selector::Late,
+            "failed" => Selector::Failed,
+            "attention" => Selector::Attention,
+            "commented" => Selector::Commented,
+            word => match word.find('=') {
+                Some(pos) => Selector::Column(word[..pos].to_string(), word[pos + 1..].to_string()),
+                None => return Err(format!("Unknown selector {}", word)),
+            },
+        });
+    }
+    Ok(selectors)
+}
+
+pub fn parse(line: &str) -> Result<Command, String> {
+    let words: Vec<&str> = line.trim_start_matches(':').split_whitespace().collect();
+    let (name, args) = match words.split_first() {
+        Some((name, args)) => (*name, args),
+        None => return Err("Empty command".to_string()),
+    };
+    match name {
+        "grade" => Ok(Command::Grade(parse_selectors(args)?)),
+        "fetch" => Ok(Command::Fetch(parse_selectors(args)?)),
+        "set" => {
+            if args.len() < 2 {
+                return Err("Usage: set blackbox|whitebox <grade|none> [selector...]".to_string());
+            }
+            let whitebox = match args[0] {
+                "blackbox" => false,
+                "whitebox" => true,
+                other => return Err(format!("Unknown grade {}", other)),
+            };
+            let grade = match args[1] {
+                "none" => None,
+                grade => Some(
+                    grade
+                        .parse::<f64>()
+                        .map_err(|_| format!("Invalid grade {}", grade))?,
+                ),
+            };
+            Ok(Command::Set(whitebox, grade, parse_selectors(&args[2..])?))
+        }
+        "export" => {
+            if args.len() != 2 {
+                return Err("Usage: export csv|json <file>".to_string());
+            }
+            let format = match args[0] {
+                "csv" => Format::Csv,
+                "json" => Format::Json,
+                other => return Err(format!("Unknown format {}", other)),
+            };
+            Ok(Command::Export(format, args[1].to_string()))
+        }
+        "branch" => match args {
+            [branch] => Ok(Command::Branch(branch.to_string())),
+            _ => Err("Usage: branch <name>".to_string()),
+        },
+        "save" | "w" => Ok(Command::Save),
+        "help" => Ok(Command::Help),
+        "quit" | "q" => Ok(Command::Quit),
+        other => Err(format!("Unknown command {}", other)),
+    }
+}
+
+// complete last word of line, returns new line and candidates if ambiguous
+pub fn complete(line: &str, columns: &[String]) -> (String, Vec<String>) {
+    let words: Vec<&str> = line.trim_start_matches(':').split_whitespace().collect();
+    // index and prefix of the word under cursor
+    let (index, prefix) = if line.ends_with(' ') || words.is_empty() {
+        (words.len(), "")
+    } else {
+        (words.len() - 1, words[words.len() - 1])
+    };
+
+    let mut selectors: Vec<String> = SELECTORS.iter().map(|s| s.to_string()).collect();
+    selectors.push("team=".to_string());
+    for column in columns.iter() {
+        selectors.push(format!("{}=", column));
+    }
+    let candidates: Vec<String> = match (index, words.first().cloned()) {
+        (0, _) => COMMANDS.iter().map(|s| s.to_string()).collect(),
+        (_, Some("grade")) | (_, Some("fetch")) => selectors,
+        (1, Some("set")) => vec!["blackbox".to_string(), "whitebox".to_string()],
+        (2, Some("set")) => vec!["none".to_string()],
+        (_, Some("set")) => selectors,
+        (1, Some("export")) => vec!["csv".to_string(), "json".to_string()],
+        _ => Vec::new(),
+    };
+    let matched: Vec<String> = candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(prefix))
+        .collect();
+
+    let base = &line[..line.len() - prefix.len()];
+    match matched.len() {
+        0 => (line.to_string(), matched),
+        1 => {
+            let word = &matched[0];
+            let space = if word.ends_with('=') { "" } else { " " };
+            (format!("{}{}{}", base, word, space), Vec::new())
+        }
+        _ => {
+            // longest common prefix of candidates
+            let mut common = matched[0].clone();
+            for word in matched.iter() {
+                while !word.starts_with(&common) {
+                    common.pop();
+                }
+            }
+            (format!("{}{}", base, common), matched)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(line: &str) -> String {
+        match parse(line) {
+            Ok(_) => panic!("{} should not parse", line),
+            Err(err) => err,
+        }
+    }
+
+    fn complete(line: &str) -> (String, Vec<String>) {
+        super::complete(line, &["section".to_string()])
+    }
+
+    #[test]
+    fn commands() {
+        assert!(matches!(parse(":save"), Ok(Command::Save)));
+        assert!(matches!(parse("w"), Ok(Command::Save)));
+        assert!(matches!(parse("q"), Ok(Command::Quit)));
+        assert!(matches!(parse("  help "), Ok(Command::Help)));
+        assert!(matches!(parse("branch dev"), Ok(Command::Branch(branch)) if branch == "dev"));
+        assert!(matches!(
+            parse("export json out.json"),
+            Ok(Command::Export(Format::Json, path)) if path == "out.json"
+        ));
+        assert!(matches!(
+            parse("set whitebox 90.5 selected"),
+            Ok(Command::Set(true, Some(grade), selectors))
+                if grade == 90.5 && matches!(selectors[..], [Selector::Selected])
+        ));
+        assert!(matches!(
+            parse("set blackbox none"),
+            Ok(Command::Set(false, None, selectors)) if selectors.is_empty()
+        ));
+    }
+
+    #[test]
+    fn selectors() {
+        match parse("grade ungraded late section=2 team=a=b") {
+            Ok(Command::Grade(selectors)) => match &selectors[..] {
+                [Selector::Ungraded, Selector::Late, Selector::Column(column, value), Selector::Column(team, name)] =>
+                {
+                    assert_eq!((column.as_str(), value.as_str()), ("section", "2"));
+                    // value is everything after the first =
+                    assert_eq!((team.as_str(), name.as_str()), ("team", "a=b"));
+                }
+                _ => panic!("wrong selectors"),
+            },
+            _ => panic!("grade should parse"),
+        }
+        assert!(matches!(parse("fetch"), Ok(Command::Fetch(selectors)) if selectors.is_empty()));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(error(":"), "Empty command");
+        assert_eq!(error("push"), "Unknown command push");
+        assert_eq!(error("grade nobody"), "Unknown selector nobody");
+        assert_eq!(
+            error("set blackbox"),
+            "Usage: set blackbox|whitebox <grade|none> [selector...]"
+        );
+        assert_eq!(error("set total 1"), "Unknown grade total");
+        assert_eq!(error("set blackbox high"), "Invalid grade high");
+        assert_eq!(error("export csv"), "Usage: export csv|json <file>");
+        assert_eq!(error("export xml out.xml"), "Unknown format xml");
+        assert_eq!(error("branch"), "Usage: branch <name>");
+    }
+
+    #[test]
+    fn completion() {
+        assert_eq!(complete("gr"), ("grade ".to_string(), Vec::new()));
+        assert_eq!(complete(":ex"), (":export ".to_string(), Vec::new()));
+        assert_eq!(
+            complete("grade se"),
+            (
+                "grade se".to_string(),
+                vec!["selected".to_string(), "section=".to_string()]
+            )
+        );
+        // no space after a column selector, the value follows
+        assert_eq!(
+            complete("grade sec"),
+            ("grade section=".to_string(), Vec::new())
+        );
+        assert_eq!(complete("set w"), ("set whitebox ".to_string(), Vec::new()));
+        assert_eq!(
+            complete("set whitebox n"),
+            ("set whitebox none ".to_string(), Vec::new())
+        );
+        assert_eq!(
+            complete("export "),
+            (
+                "export ".to_string(),
+                vec!["csv".to_string(), "json".to_string()]
+            )
+        );
+        assert_eq!(
+            complete("fetch c"),
+            ("fetch commented ".to_string(), Vec::new())
+        );
+        assert_eq!(complete("save x"), ("save x".to_string(), Vec::new()));
+        assert_eq!(complete("zz"), ("zz".to_string(), Vec::new()));
+    }
+}
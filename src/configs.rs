@@ -1,5 +1,8 @@
+use crate::keys::KeyMap;
+use crate::results::Format;
 use chrono::{DateTime, Utc};
-use clap::{App, AppSettings, Arg};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
@@ -29,93 +32,331 @@ pub struct Config {
     pub copy: Vec<String>,
     // deadline
     pub deadline: Option<DateTime<Utc>>,
+    // header names of students csv columns
+    pub columns: Columns,
+    // path to teams.csv of group assignments
+    pub teams: Option<String>,
+    // path to snippets.csv of predefined comments
+    pub snippets: Option<String>,
+    // path to rubric.toml of whitebox criteria
+    pub rubric: Option<String>,
+    // seconds between autosaves of unsaved edits to journal, 0 to disable
+    pub autosave: u64,
+    // full whitebox grade, deductions start from it
+    pub whitebox_max: f64,
+    // formula of total grade
+    pub total: Option<String>,
+    // points deducted per started day after deadline, as late_penalty in total
+    pub late_penalty: f64,
+    // columns and formatting of results csv
+    pub result_format: Format,
+    // splitting students among TAs
+    pub tas: Tas,
+    // only show, fetch and grade students of this TA
+    pub ta: Option<String>,
+    // key bindings
+    pub keys: KeyMap,
 }
 
-impl Config {
-    pub fn new() -> Config {
-        let args = App::new("classroom-helper")
-            .about("GitHub Classroom helper")
-            .author(crate_authors!())
-            .version(crate_version!())
-            .setting(AppSettings::ColoredHelp)
-            .arg(
-                Arg::with_name("organization")
-                    .short("o")
-                    .long("organization")
-                    .value_name("org")
-                    .help("GitHub organization name")
-                    .takes_value(true),
-            )
-            .arg(
-                Arg::with_name("prefix")
-                    .short("p")
-                    .long("prefix")
-                    .value_name("prefix")
-                    .help("GitHub repo prefix")
-                    .takes_value(true),
-            )
-            .arg(
-                Arg::with_name("students")
-                    .short("s")
-                    .long("students")
-                    .value_name("students")
-                    .help("Path to students csv")
-                    .takes_value(true),
-            )
-            .arg(
-                Arg::with_name("workspace")
-                    .short("w")
-                    .long("workspace")
-                    .value_name("workspace")
-                    .help("Path to workspace csv")
-                    .takes_value(true),
-            )
-            .arg(
-                Arg::with_name("template")
-                    .short("t")
-                    .long("template")
-                    .value_name("template")
-                    .help("Template repo slug")
-                    .takes_value(true),
-            )
-            .arg(
-                Arg::with_name("template_branch")
-                    .short("b")
-                    .long("template_branch")
-                    .value_name("template_branch")
-                    .help("Template repo branch")
-                    .takes_value(true),
-            )
-            .arg(
-                Arg::with_name("result")
-                    .short("r")
-                    .long("result")
-                    .value_name("result")
-                    .help("Result csv path")
-                    .takes_value(true),
-            )
-            .arg(
-                Arg::with_name("grader")
-                    .short("g")
-                    .long("grader")
-                    .value_name("grader")
-                    .help("Grader py name")
-                    .takes_value(true),
-            )
-            .arg(
-                Arg::with_name("before_grader")
-                    .long("before_grader")
-                    .value_name("before_grader")
-                    .help("Anything to run before grader")
-                    .takes_value(true),
-            )
-            .arg(
-                Arg::with_name("config")
-                    .value_name("config")
-                    .help("Config file"),
-            )
-            .get_matches();
+#[derive(Clone, Default)]
+pub struct Tas {
+    // TAs to deal students to in turn
+    pub names: Vec<String>,
+    // header of roster column to split by, e.g. section
+    pub column: Option<String>,
+    // TA of each value of column, remaining values are dealt in turn
+    pub sections: HashMap<String, String>,
+    // csv of student id or GitHub and TA, overriding names and column
+    pub file: Option<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct Columns {
+    // header of student id column, first column if None
+    pub student_id: Option<String>,
+    // header of name column, second column if None
+    pub name: Option<String>,
+    // header of github column, third column if None
+    pub github: Option<String>,
+    // headers of extra columns, all remaining columns if None
+    pub extra: Option<Vec<String>>,
+}
+
+pub fn args() -> ArgMatches<'static> {
+    App::new("classroom-helper")
+        .about("GitHub Classroom helper")
+        .author(crate_authors!())
+        .version(crate_version!())
+        .setting(AppSettings::ColoredHelp)
+        .arg(
+            Arg::with_name("organization")
+                .short("o")
+                .long("organization")
+                .value_name("org")
+                .help("GitHub organization name")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("prefix")
+                .short("p")
+                .long("prefix")
+                .value_name("prefix")
+                .help("GitHub repo prefix")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("students")
+                .short("s")
+                .long("students")
+                .value_name("students")
+                .help("Path to students csv")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("workspace")
+                .short("w")
+                .long("workspace")
+                .value_name("workspace")
+                .help("Path to workspace csv")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("template")
+                .short("t")
+                .long("template")
+                .value_name("template")
+                .help("Template repo slug")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("template_branch")
+                .short("b")
+                .long("template_branch")
+                .value_name("template_branch")
+                .help("Template repo branch")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("result")
+                .short("r")
+                .long("result")
+                .value_name("result")
+                .help("Result csv path")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("grader")
+                .short("g")
+                .long("grader")
+                .value_name("grader")
+                .help("Grader py name")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("before_grader")
+                .long("before_grader")
+                .value_name("before_grader")
+                .help("Anything to run before grader")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("teams")
+                .long("teams")
+                .value_name("teams")
+                .help("Path to teams csv of group assignments")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ta")
+                .long("ta")
+                .value_name("ta")
+                .help("Only grade students of this TA, saving results to <result>-<ta>.csv")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("config")
+                .value_name("config")
+                .help("Config file"),
+        )
+        .subcommand(
+            SubCommand::with_name("gradebook")
+                .about("Course-wide gradebook over all assignments")
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("output")
+                        .help("Gradebook csv path")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("export")
+                        .short("e")
+                        .long("export")
+                        .help("Export gradebook csv without starting tui"),
+                )
+                .arg(
+                    Arg::with_name("config")
+                        .value_name("config")
+                        .help("Gradebook config file")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Import GitHub Classroom roster and assignment exports into students csv")
+                .arg(
+                    Arg::with_name("roster")
+                        .long("roster")
+                        .value_name("roster")
+                        .help("Path to GitHub Classroom roster csv")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("assignment")
+                        .long("assignment")
+                        .value_name("assignment")
+                        .help("Path to GitHub Classroom assignment grades csv")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("dry_run")
+                        .short("n")
+                        .long("dry_run")
+                        .help("Only report changes without writing students csv"),
+                )
+                .arg(
+                    Arg::with_name("config")
+                        .value_name("config")
+                        .help("Config file")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("assign")
+                .about("Show TA of each student as split by config")
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("output")
+                        .help("Save TAs to csv, to be used as tas.file")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("config")
+                        .value_name("config")
+                        .help("Config file")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("merge")
+                .about("Merge results csv files graded by several TAs")
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("output")
+                        .help("Merged results csv path")
+                        .takes_value(true)
+                        .required_unless("dry_run"),
+                )
+                .arg(
+                    Arg::with_name("prefer")
+                        .long("prefer")
+                        .value_name("rules")
+                        .help("Resolve conflicts by rules in order, others are asked")
+                        .takes_value(true)
+                        .require_delimiter(true)
+                        .multiple(true)
+                        .possible_values(&["latest"]),
+                )
+                .arg(
+                    Arg::with_name("missing")
+                        .long("missing")
+                        .value_name("missing")
+                        .help("Value written for missing grades")
+                        .default_value("N/A"),
+                )
+                .arg(
+                    Arg::with_name("dry_run")
+                        .short("n")
+                        .long("dry_run")
+                        .help("Only report conflicts without writing merged csv"),
+                )
+                .arg(
+                    Arg::with_name("inputs")
+                        .value_name("inputs")
+                        .help("Results csv files to merge")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .get_matches()
+}
+
+// layout of results csv in [result_csv] section
+pub fn result_format(settings: &config::Config) -> Format {
+    let default = Format::default();
+    Format {
+        columns: settings
+            .get_array("result_csv.columns")
+            .map_or(default.columns, |values| {
+                values
+                    .into_iter()
+                    .map(|value| value.into_str().unwrap())
+                    .collect()
+            }),
+        language: settings
+            .get_str("result_csv.language")
+            .unwrap_or(default.language),
+        headers: settings
+            .get_table("result_csv.headers")
+            .map_or(default.headers, |table| {
+                table
+                    .into_iter()
+                    .map(|(name, header)| (name, header.into_str().unwrap()))
+                    .collect()
+            }),
+        bom: settings.get_bool("result_csv.bom").unwrap_or(default.bom),
+        delimiter: settings.get_str("result_csv.delimiter").ok().map_or(
+            default.delimiter,
+            |delimiter| match delimiter.as_str() {
+                "tab" | "\\t" => b'\t',
+                delimiter => delimiter.bytes().next().unwrap_or(b','),
+            },
+        ),
+        missing: settings
+            .get_str("result_csv.missing")
+            .unwrap_or(default.missing),
+        precision: settings
+            .get_int("result_csv.precision")
+            .ok()
+            .map(|precision| precision as usize),
+    }
+}
+
+// key bindings in [keys] section
+pub fn key_map(settings: &config::Config) -> KeyMap {
+    let mut keys = HashMap::new();
+    if let Ok(table) = settings.get_table("keys") {
+        for (action, value) in table.into_iter() {
+            let values = match value.clone().into_array() {
+                Ok(values) => values
+                    .into_iter()
+                    .map(|value| value.into_str().unwrap())
+                    .collect(),
+                Err(_) => vec![value.into_str().unwrap()],
+            };
+            keys.insert(action, values);
+        }
+    }
+    KeyMap::new(&keys)
+}
 
+impl Config {
+    pub fn new(args: &ArgMatches) -> Config {
         let mut settings = config::Config::default();
 
         // Precedence
@@ -141,11 +382,13 @@ impl Config {
             "result",
             "grader",
             "before_grader",
+            "teams",
+            "ta",
         ]
         .iter()
         {
             if let Some(value) = args.value_of(key) {
-                clap_args.set(*key, value).unwrap();
+                clap_args.set(key, value).unwrap();
                 overwrite = true;
             }
         }
@@ -160,17 +403,69 @@ impl Config {
         let template = settings.get_str("template").unwrap();
         let template_branch = settings
             .get_str("template_branch")
-            .unwrap_or(format!("master"));
+            .unwrap_or("master".to_string());
         let workspace = settings.get_str("workspace").unwrap();
-        let results = settings.get_str("result").unwrap();
+        let mut results = settings.get_str("result").unwrap();
         let grader = settings.get_str("grader").unwrap();
         let before_grader = settings.get_str("before_grader").ok();
+        let teams = settings.get_str("teams").ok();
+        let snippets = settings.get_str("snippets").ok();
+        let rubric = settings.get_str("rubric").ok();
+        let autosave = settings.get_int("autosave").unwrap_or(5) as u64;
+        let whitebox_max = settings.get_float("whitebox_max").unwrap_or(100.0);
         let diff = settings.get_str("diff").unwrap();
         let copy_values = settings.get_array("copy").unwrap();
         let deadline = settings
             .get_str("deadline")
             .ok()
             .and_then(|s| s.parse::<DateTime<Utc>>().ok());
+        let columns = Columns {
+            student_id: settings.get_str("columns.student_id").ok(),
+            name: settings.get_str("columns.name").ok(),
+            github: settings.get_str("columns.github").ok(),
+            extra: settings.get_array("columns.extra").ok().map(|values| {
+                values
+                    .into_iter()
+                    .map(|value| value.into_str().unwrap())
+                    .collect()
+            }),
+        };
+        let strings = |key: &str| -> Vec<String> {
+            settings.get_array(key).map_or(Vec::new(), |values| {
+                values
+                    .into_iter()
+                    .map(|value| value.into_str().unwrap())
+                    .collect()
+            })
+        };
+        let tas = Tas {
+            names: strings("tas.names"),
+            column: settings.get_str("tas.column").ok(),
+            sections: settings
+                .get_table("tas.sections")
+                .map_or(HashMap::new(), |table| {
+                    table
+                        .into_iter()
+                        .map(|(value, ta)| (value, ta.into_str().unwrap()))
+                        .collect()
+                }),
+            file: settings.get_str("tas.file").ok(),
+        };
+        let total = settings.get_str("total").ok();
+        let late_penalty = settings.get_float("late_penalty").unwrap_or(0.0);
+        let result_format = result_format(&settings);
+        let ta = settings.get_str("ta").ok();
+        // each TA saves own results, merged later
+        if let Some(ta) = &ta {
+            let path = Path::new(&results);
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let name = match path.extension() {
+                Some(ext) => format!("{}-{}.{}", stem, ta, ext.to_string_lossy()),
+                None => format!("{}-{}", stem, ta),
+            };
+            results = path.with_file_name(name).to_string_lossy().to_string();
+        }
+        let keys = key_map(&settings);
         let mut copy = Vec::new();
 
         fs::create_dir_all(Path::new(&workspace)).unwrap();
@@ -192,6 +487,18 @@ impl Config {
             copy,
             before_grader,
             deadline,
+            columns,
+            teams,
+            snippets,
+            rubric,
+            autosave,
+            whitebox_max,
+            total,
+            late_penalty,
+            result_format,
+            tas,
+            ta,
+            keys,
         }
     }
 }
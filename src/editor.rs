@@ -0,0 +1,251 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_width::UnicodeWidthStr;
+
+// multi-line text with a cursor, used for comments
+#[derive(Default)]
+pub struct Editor {
+    pub text: String,
+    // byte offset in text, always on a char boundary
+    cursor: usize,
+}
+
+impl Editor {
+    pub fn new(text: String) -> Editor {
+        let cursor = text.len();
+        Editor { text, cursor }
+    }
+
+    // add text as a new line at the end
+    pub fn append_line(&mut self, line: &str) {
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+        self.text.push_str(line);
+        self.cursor = self.text.len();
+    }
+
+    // byte offset of the start of line containing cursor
+    fn line_start(&self) -> usize {
+        self.text[..self.cursor]
+            .rfind('\n')
+            .map_or(0, |pos| pos + 1)
+    }
+
+    fn line_end(&self) -> usize {
+        self.text[self.cursor..]
+            .find('\n')
+            .map_or(self.text.len(), |pos| self.cursor + pos)
+    }
+
+    fn prev_char(&self) -> Option<usize> {
+        self.text[..self.cursor]
+            .char_indices()
+            .next_back()
+            .map(|(pos, _)| pos)
+    }
+
+    fn next_char(&self) -> Option<usize> {
+        self.text[self.cursor..]
+            .chars()
+            .next()
+            .map(|ch| self.cursor + ch.len_utf8())
+    }
+
+    // start of the word before cursor, skipping spaces first
+    fn prev_word(&self) -> usize {
+        let before = &self.text[..self.cursor];
+        let trimmed = before.trim_end_matches(|ch: char| ch.is_whitespace() && ch != '\n');
+        if trimmed.ends_with('\n') {
+            // delete spaces at line start, or the line break itself
+            return if trimmed.len() < before.len() {
+                trimmed.len()
+            } else {
+                trimmed.len() - 1
+            };
+        }
+        trimmed
+            .char_indices()
+            .rev()
+            .find(|(_, ch)| ch.is_whitespace())
+            .map_or(0, |(pos, ch)| pos + ch.len_utf8())
+    }
+
+    // move cursor to char column of line starting at start
+    fn goto_column(&mut self, start: usize, column: usize) {
+        let line = &self.text[start..];
+        let line = &line[..line.find('\n').unwrap_or(line.len())];
+        self.cursor = start
+            + line
+                .char_indices()
+                .nth(column)
+                .map_or(line.len(), |(pos, _)| pos);
+    }
+
+    fn up(&mut self) {
+        let start = self.line_start();
+        if start == 0 {
+            return;
+        }
+        let column = self.text[start..self.cursor].chars().count();
+        let prev_start = self.text[..start - 1].rfind('\n').map_or(0, |pos| pos + 1);
+        self.goto_column(prev_start, column);
+    }
+
+    fn down(&mut self) {
+        let end = self.line_end();
+        if end == self.text.len() {
+            return;
+        }
+        let column = self.text[self.line_start()..self.cursor].chars().count();
+        self.goto_column(end + 1, column);
+    }
+
+    fn insert(&mut self, ch: char) {
+        self.text.insert(self.cursor, ch);
+        self.cursor += ch.len_utf8();
+    }
+
+    // delete from start to cursor
+    fn delete_to(&mut self, start: usize) {
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    // line and display column of cursor
+    pub fn cursor_position(&self) -> (usize, usize) {
+        let before = &self.text[..self.cursor];
+        let line = before.matches('\n').count();
+        (line, before[self.line_start()..].width())
+    }
+
+    // returns false if key is not an editing key
+    pub fn handle(&mut self, event: &KeyEvent) -> bool {
+        let ctrl = event.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = event.modifiers.contains(KeyModifiers::ALT);
+        match event.code {
+            KeyCode::Enter if alt => self.insert('\n'),
+            KeyCode::Char('j') if ctrl => self.insert('\n'),
+            KeyCode::Char('w') if ctrl => self.delete_to(self.prev_word()),
+            KeyCode::Backspace if alt => self.delete_to(self.prev_word()),
+            KeyCode::Char('u') if ctrl => self.delete_to(self.line_start()),
+            KeyCode::Char('a') if ctrl => self.cursor = self.line_start(),
+            KeyCode::Char(ch) if !ctrl && !alt => self.insert(ch),
+            KeyCode::Backspace => {
+                if let Some(pos) = self.prev_char() {
+                    self.text.remove(pos);
+                    self.cursor = pos;
+                }
+            }
+            KeyCode::Delete => {
+                if self.next_char().is_some() {
+                    self.text.remove(self.cursor);
+                }
+            }
+            KeyCode::Left if ctrl || alt => self.cursor = self.prev_word(),
+            KeyCode::Left => self.cursor = self.prev_char().unwrap_or(self.cursor),
+            KeyCode::Right => self.cursor = self.next_char().unwrap_or(self.cursor),
+            KeyCode::Home => self.cursor = self.line_start(),
+            KeyCode::End => self.cursor = self.line_end(),
+            KeyCode::Up => self.up(),
+            KeyCode::Down => self.down(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // type keys like "a", "enter", "ctrl-w" into editor starting with text, cursor at end
+    fn edit(text: &str, keys: &[&str]) -> Editor {
+        let mut editor = Editor::new(text.to_string());
+        for key in keys.iter() {
+            let (modifiers, name) = match key.split_once('-') {
+                Some(("ctrl", name)) => (KeyModifiers::CONTROL, name),
+                Some(("alt", name)) => (KeyModifiers::ALT, name),
+                _ => (KeyModifiers::NONE, *key),
+            };
+            let code = match name {
+                "enter" => KeyCode::Enter,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                name => KeyCode::Char(name.chars().next().unwrap()),
+            };
+            editor.handle(&KeyEvent { code, modifiers });
+        }
+        editor
+    }
+
+    #[test]
+    fn insert_and_delete() {
+        assert_eq!(edit("ab", &["c"]).text, "abc");
+        assert_eq!(edit("ab", &["left", "x"]).text, "axb");
+        assert_eq!(edit("ab", &["alt-enter", "c"]).text, "ab\nc");
+        assert_eq!(edit("ab", &["ctrl-j"]).text, "ab\n");
+        assert_eq!(edit("a好", &["backspace"]).text, "a");
+        assert_eq!(edit("a好", &["left", "delete"]).text, "a");
+        assert_eq!(edit("ab", &["home", "backspace"]).text, "ab");
+        assert_eq!(edit("ab", &["delete"]).text, "ab");
+    }
+
+    #[test]
+    fn delete_word_and_line() {
+        assert_eq!(edit("foo bar  ", &["ctrl-w"]).text, "foo ");
+        assert_eq!(edit("foo bar", &["alt-backspace"]).text, "foo ");
+        // spaces at line start, then the line break
+        assert_eq!(edit("foo\n  ", &["ctrl-w"]).text, "foo\n");
+        assert_eq!(edit("foo\n", &["ctrl-w"]).text, "foo");
+        assert_eq!(edit("foo\nbar baz", &["ctrl-u"]).text, "foo\n");
+        assert_eq!(edit("foo bar", &["ctrl-left", "x"]).text, "foo xbar");
+    }
+
+    #[test]
+    fn move_between_lines() {
+        let editor = edit("long line\nab", &["up"]);
+        assert_eq!(editor.cursor_position(), (0, 2));
+        // column is clamped to shorter line
+        let editor = edit("long line\nab", &["up", "end", "down"]);
+        assert_eq!(editor.cursor_position(), (1, 2));
+        let editor = edit("ab\ncd", &["up", "up", "ctrl-a"]);
+        assert_eq!(editor.cursor_position(), (0, 0));
+        // display width of wide chars
+        let editor = edit("好好\nab", &["up"]);
+        assert_eq!(editor.cursor_position(), (0, 4));
+    }
+
+    #[test]
+    fn append_line() {
+        let mut editor = Editor::new("first".to_string());
+        editor.append_line("second");
+        assert_eq!(editor.text, "first\nsecond");
+        editor.append_line("third");
+        assert_eq!(editor.cursor_position(), (2, 5));
+        let mut editor = Editor::default();
+        editor.append_line("only");
+        assert_eq!(editor.text, "only");
+    }
+
+    #[test]
+    fn unhandled_keys() {
+        let mut editor = Editor::default();
+        let esc = KeyEvent {
+            code: KeyCode::Esc,
+            modifiers: KeyModifiers::NONE,
+        };
+        assert!(!editor.handle(&esc));
+        let ctrl_s = KeyEvent {
+            code: KeyCode::Char('s'),
+            modifiers: KeyModifiers::CONTROL,
+        };
+        assert!(!editor.handle(&ctrl_s));
+        assert_eq!(editor.text, "");
+    }
+}
@@ -1,15 +1,22 @@
 use crossterm::event;
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
 use std::time::Duration;
 
 pub enum Event {
     Input(event::KeyEvent),
+    Mouse(event::MouseEvent),
+    // terminal resized to columns, rows
+    Resize(u16, u16),
     Tick,
 }
 
 pub struct Events {
     rx: mpsc::Receiver<Event>,
+    // input thread stops reading while another program owns the terminal
+    paused: Arc<AtomicBool>,
+    idle: Arc<AtomicBool>,
     _input_handle: thread::JoinHandle<()>,
     _tick_handle: thread::JoinHandle<()>,
 }
@@ -17,16 +24,30 @@ pub struct Events {
 impl Events {
     pub fn new() -> Events {
         let (tx, rx) = mpsc::channel();
+        let paused = Arc::new(AtomicBool::new(false));
+        let idle = Arc::new(AtomicBool::new(false));
         let input_handle = {
             let tx = tx.clone();
+            let paused = paused.clone();
+            let idle = idle.clone();
             thread::spawn(move || loop {
-                match event::read() {
-                    Ok(event::Event::Key(event)) => {
-                        if let Err(_) = tx.send(Event::Input(event)) {
-                            return;
-                        }
-                    }
-                    _ => {}
+                if paused.load(Ordering::SeqCst) {
+                    idle.store(true, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(10));
+                    continue;
+                }
+                // poll with timeout to notice pausing
+                if !event::poll(Duration::from_millis(50)).unwrap_or(false) {
+                    continue;
+                }
+                let event = match event::read() {
+                    Ok(event::Event::Key(event)) => Event::Input(event),
+                    Ok(event::Event::Mouse(event)) => Event::Mouse(event),
+                    Ok(event::Event::Resize(columns, rows)) => Event::Resize(columns, rows),
+                    _ => continue,
+                };
+                if tx.send(event).is_err() {
+                    return;
                 }
             })
         };
@@ -42,11 +63,26 @@ impl Events {
         };
         Events {
             rx,
+            paused,
+            idle,
             _input_handle: input_handle,
             _tick_handle: tick_handle,
         }
     }
 
+    // wait until input thread stops reading the terminal
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        while !self.idle.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    pub fn resume(&self) {
+        self.idle.store(false, Ordering::SeqCst);
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
     pub fn next(&self) -> Result<Event, mpsc::RecvError> {
         self.rx.recv()
     }
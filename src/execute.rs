@@ -1,7 +1,7 @@
 use std::path::Path;
 use std::process::{Command, Stdio};
 
-pub fn run<'a, PP: AsRef<Path>>(script: &'a str, pwd: PP) -> String {
+pub fn run<PP: AsRef<Path>>(script: &str, pwd: PP) -> String {
     let interpreter = if script.ends_with("sh") {
         "bash"
     } else {
@@ -0,0 +1,409 @@
+// total grade expression, e.g. "0.7 * blackbox + 0.3 * min(whitebox, 100) - late_penalty"
+use std::collections::HashMap;
+
+pub enum Expr {
+    Number(f64),
+    Variable(String),
+    Negate(Box<Expr>),
+    // operator, left and right
+    Binary(String, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+// functions and their number of arguments, None for any
+const FUNCTIONS: [(&str, Option<usize>); 9] = [
+    ("min", None),
+    ("max", None),
+    ("abs", Some(1)),
+    ("floor", Some(1)),
+    ("ceil", Some(1)),
+    ("round", None),
+    ("clamp", Some(3)),
+    ("if", Some(3)),
+    ("default", Some(2)),
+];
+
+// variables given to every formula, rubric criteria must not map to these
+pub const RESERVED: [&str; 5] = ["blackbox", "whitebox", "late", "days_late", "late_penalty"];
+
+// variable name of a rubric criterion, e.g. "Error handling" becomes error_handling
+pub fn variable(name: &str) -> String {
+    name.trim()
+        .to_lowercase()
+        .chars()
+        .map(|ch| if ch.is_alphanumeric() { ch } else { '_' })
+        .collect()
+}
+
+#[derive(PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    // operator or parenthesis
+    Symbol(String),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let ch = chars[pos];
+        if ch.is_whitespace() {
+            pos += 1;
+        } else if ch.is_ascii_digit() || ch == '.' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+                pos += 1;
+            }
+            let number: String = chars[start..pos].iter().collect();
+            tokens.push(Token::Number(
+                number
+                    .parse()
+                    .map_err(|_| format!("Invalid number {}", number))?,
+            ));
+        } else if ch.is_alphabetic() || ch == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            tokens.push(Token::Ident(chars[start..pos].iter().collect()));
+        } else {
+            let two: String = chars[pos..std::cmp::min(pos + 2, chars.len())]
+                .iter()
+                .collect();
+            if ["<=", ">=", "==", "!="].contains(&two.as_str()) {
+                tokens.push(Token::Symbol(two));
+                pos += 2;
+            } else if "+-*/(),<>".contains(ch) {
+                tokens.push(Token::Symbol(ch.to_string()));
+                pos += 1;
+            } else {
+                return Err(format!("Unexpected {} in formula", ch));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    variables: &'a [String],
+}
+
+impl<'a> Parser<'a> {
+    fn peek_symbol(&self, symbols: &[&str]) -> Option<String> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Symbol(symbol)) if symbols.contains(&symbol.as_str()) => {
+                Some(symbol.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        if self.peek_symbol(&[symbol]).is_none() {
+            return Err(format!("Expected {} in formula", symbol));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // comparison of sums, 1 if true and 0 if false
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.sum()?;
+        match self.peek_symbol(&["<", ">", "<=", ">=", "==", "!="]) {
+            Some(op) => {
+                self.pos += 1;
+                let right = self.sum()?;
+                Ok(Expr::Binary(op, Box::new(left), Box::new(right)))
+            }
+            None => Ok(left),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut left = self.term()?;
+        while let Some(op) = self.peek_symbol(&["+", "-"]) {
+            self.pos += 1;
+            let right = self.term()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while let Some(op) = self.peek_symbol(&["*", "/"]) {
+            self.pos += 1;
+            let right = self.unary()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek_symbol(&["-"]).is_some() {
+            self.pos += 1;
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let token = match self.tokens.get(self.pos) {
+            Some(token) => token,
+            None => return Err("Unexpected end of formula".to_string()),
+        };
+        self.pos += 1;
+        match token {
+            Token::Number(number) => Ok(Expr::Number(*number)),
+            Token::Symbol(symbol) if symbol == "(" => {
+                let expr = self.comparison()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Symbol(symbol) => Err(format!("Unexpected {} in formula", symbol)),
+            Token::Ident(name) => {
+                let name = name.clone();
+                if self.peek_symbol(&["("]).is_none() {
+                    if !self.variables.contains(&name) {
+                        return Err(format!("Unknown variable {} in formula", name));
+                    }
+                    return Ok(Expr::Variable(name));
+                }
+                let arity = match FUNCTIONS.iter().find(|(function, _)| *function == name) {
+                    Some((_, arity)) => *arity,
+                    None => return Err(format!("Unknown function {} in formula", name)),
+                };
+                self.pos += 1;
+                let mut args = Vec::new();
+                if self.peek_symbol(&[")"]).is_none() {
+                    args.push(self.comparison()?);
+                    while self.peek_symbol(&[","]).is_some() {
+                        self.pos += 1;
+                        args.push(self.comparison()?);
+                    }
+                }
+                self.expect(")")?;
+                let valid = match (name.as_str(), arity) {
+                    ("round", _) => args.len() == 1 || args.len() == 2,
+                    (_, Some(arity)) => args.len() == arity,
+                    (_, None) => !args.is_empty(),
+                };
+                if !valid {
+                    return Err(format!("Wrong number of arguments to {} in formula", name));
+                }
+                Ok(Expr::Call(name, args))
+            }
+        }
+    }
+}
+
+// parse formula using only given variables
+pub fn parse(text: &str, variables: &[String]) -> Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        pos: 0,
+        variables,
+    };
+    let expr = parser.comparison()?;
+    if parser.pos < parser.tokens.len() {
+        return Err("Unexpected trailing input in formula".to_string());
+    }
+    Ok(expr)
+}
+
+impl Expr {
+    // None if a grade used is missing, unless replaced by default(),
+    // or if a value is not finite, e.g. divided by zero
+    pub fn eval(&self, variables: &HashMap<String, Option<f64>>) -> Option<f64> {
+        self.value(variables).filter(|value| value.is_finite())
+    }
+
+    fn value(&self, variables: &HashMap<String, Option<f64>>) -> Option<f64> {
+        match self {
+            Expr::Number(number) => Some(*number),
+            Expr::Variable(name) => variables.get(name).cloned().flatten(),
+            Expr::Negate(expr) => expr.eval(variables).map(|value| -value),
+            Expr::Binary(op, left, right) => {
+                let left = left.eval(variables)?;
+                let right = right.eval(variables)?;
+                let truth = |value: bool| if value { 1.0 } else { 0.0 };
+                Some(match op.as_str() {
+                    "+" => left + right,
+                    "-" => left - right,
+                    "*" => left * right,
+                    "/" => left / right,
+                    "<" => truth(left < right),
+                    ">" => truth(left > right),
+                    "<=" => truth(left <= right),
+                    ">=" => truth(left >= right),
+                    "==" => truth(left == right),
+                    _ => truth(left != right),
+                })
+            }
+            Expr::Call(name, args) => {
+                if name == "default" {
+                    return args[0].eval(variables).or_else(|| args[1].eval(variables));
+                }
+                if name == "if" {
+                    return if args[0].eval(variables)? != 0.0 {
+                        args[1].eval(variables)
+                    } else {
+                        args[2].eval(variables)
+                    };
+                }
+                let values: Vec<f64> = args
+                    .iter()
+                    .map(|arg| arg.eval(variables))
+                    .collect::<Option<_>>()?;
+                Some(match name.as_str() {
+                    "min" => values.iter().cloned().fold(f64::INFINITY, f64::min),
+                    "max" => values.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+                    "abs" => values[0].abs(),
+                    "floor" => values[0].floor(),
+                    "ceil" => values[0].ceil(),
+                    "clamp" => values[0].max(values[1]).min(values[2]),
+                    _ => {
+                        // round to given decimal places
+                        let scale = 10f64.powf(values.get(1).cloned().unwrap_or(0.0));
+                        (values[0] * scale).round() / scale
+                    }
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VARIABLES: [&str; 3] = ["blackbox", "whitebox", "late_penalty"];
+
+    fn variables() -> Vec<String> {
+        VARIABLES.iter().map(|name| name.to_string()).collect()
+    }
+
+    // evaluate formula with blackbox 80, whitebox 90 and late_penalty 5 unless given
+    fn eval(text: &str, grades: &[(&str, Option<f64>)]) -> Option<f64> {
+        let mut values: HashMap<String, Option<f64>> = [
+            ("blackbox", Some(80.0)),
+            ("whitebox", Some(90.0)),
+            ("late_penalty", Some(5.0)),
+        ]
+        .iter()
+        .map(|(name, value)| (name.to_string(), *value))
+        .collect();
+        for (name, value) in grades.iter() {
+            values.insert(name.to_string(), *value);
+        }
+        parse(text, &variables()).unwrap().eval(&values)
+    }
+
+    fn error(text: &str) -> String {
+        match parse(text, &variables()) {
+            Ok(_) => panic!("{} should not parse", text),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3", &[]), Some(7.0));
+        assert_eq!(eval("(1 + 2) * 3", &[]), Some(9.0));
+        assert_eq!(eval("10 - 4 - 3", &[]), Some(3.0));
+        assert_eq!(eval("8 / 4 / 2", &[]), Some(1.0));
+        assert_eq!(eval("-2 * -3", &[]), Some(6.0));
+        assert_eq!(eval("--1", &[]), Some(1.0));
+        assert_eq!(eval("1 + 2 > 2", &[]), Some(1.0));
+        assert_eq!(eval("1 + 1 == 3", &[]), Some(0.0));
+        assert_eq!(
+            eval("0.7 * blackbox + 0.3 * whitebox - late_penalty", &[]),
+            Some(0.7 * 80.0 + 0.3 * 90.0 - 5.0)
+        );
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(eval("min(blackbox, whitebox, 85)", &[]), Some(80.0));
+        assert_eq!(eval("max(blackbox, whitebox)", &[]), Some(90.0));
+        assert_eq!(eval("abs(-3)", &[]), Some(3.0));
+        assert_eq!(eval("floor(2.7) + ceil(2.2)", &[]), Some(5.0));
+        assert_eq!(eval("clamp(120, 0, 100)", &[]), Some(100.0));
+        assert_eq!(eval("round(2.5)", &[]), Some(3.0));
+        assert_eq!(eval("round(1.234, 2)", &[]), Some(1.23));
+        assert_eq!(eval("if(blackbox >= 60, 1, 2)", &[]), Some(1.0));
+        assert_eq!(eval("if(blackbox < 60, 1, 2)", &[]), Some(2.0));
+    }
+
+    #[test]
+    fn missing_variable() {
+        let missing = [("whitebox", None)];
+        assert_eq!(eval("whitebox", &missing), None);
+        assert_eq!(eval("blackbox + whitebox", &missing), None);
+        assert_eq!(eval("-whitebox", &missing), None);
+        assert_eq!(eval("max(blackbox, whitebox)", &missing), None);
+        assert_eq!(eval("if(whitebox > 0, 1, 2)", &missing), None);
+        // only the branch taken is evaluated
+        assert_eq!(eval("if(blackbox > 0, 1, whitebox)", &missing), Some(1.0));
+    }
+
+    #[test]
+    fn not_finite() {
+        assert_eq!(eval("blackbox / 0", &[]), None);
+        assert_eq!(eval("0 / 0", &[]), None);
+        assert_eq!(eval("min(blackbox / 0, 5)", &[]), None);
+        assert_eq!(eval("default(1 / 0, 0)", &[]), Some(0.0));
+        assert_eq!(eval("round(1, 400)", &[]), None);
+    }
+
+    #[test]
+    fn default() {
+        let missing = [("whitebox", None)];
+        assert_eq!(eval("default(whitebox, 0)", &missing), Some(0.0));
+        assert_eq!(eval("default(whitebox, 0)", &[]), Some(90.0));
+        assert_eq!(
+            eval("blackbox + default(whitebox, blackbox)", &missing),
+            Some(160.0)
+        );
+        assert_eq!(eval("default(whitebox, whitebox)", &missing), None);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(error("total + 1"), "Unknown variable total in formula");
+        assert_eq!(error("sqrt(4)"), "Unknown function sqrt in formula");
+        assert_eq!(
+            error("abs(1, 2)"),
+            "Wrong number of arguments to abs in formula"
+        );
+        assert_eq!(
+            error("min()"),
+            "Wrong number of arguments to min in formula"
+        );
+        assert_eq!(
+            error("round(1, 2, 3)"),
+            "Wrong number of arguments to round in formula"
+        );
+        assert_eq!(
+            error("if(1, 2)"),
+            "Wrong number of arguments to if in formula"
+        );
+        assert_eq!(error("1 2"), "Unexpected trailing input in formula");
+        assert_eq!(error("1 +"), "Unexpected end of formula");
+        assert_eq!(error(""), "Unexpected end of formula");
+        assert_eq!(error("(1 + 2"), "Expected ) in formula");
+        assert_eq!(error("1 + * 2"), "Unexpected * in formula");
+        assert_eq!(error("1 % 2"), "Unexpected % in formula");
+        assert_eq!(error("1..2"), "Invalid number 1..2");
+    }
+
+    #[test]
+    fn variable_names() {
+        assert_eq!(variable(" Error handling "), "error_handling");
+        assert_eq!(variable("I/O"), "i_o");
+    }
+}
@@ -0,0 +1,376 @@
+use crate::configs;
+use crate::keys::{Action, KeyMap};
+use crate::results::{self, Column};
+use clap::ArgMatches;
+use crossterm::event::KeyEvent;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Clone)]
+pub struct Assignment {
+    // column title in gradebook
+    pub name: String,
+    // result csv of this assignment
+    pub results: String,
+    // weight in course total
+    pub weight: f64,
+    // full grade of this assignment
+    pub max: f64,
+    // multiplier of blackbox grade
+    pub blackbox: f64,
+    // multiplier of whitebox grade
+    pub whitebox: f64,
+    // drop_lowest group, e.g. homework
+    pub group: Option<String>,
+    // headers of result csv relabeled in its config
+    pub format: results::Format,
+}
+
+#[derive(Clone)]
+pub struct GradebookConfig {
+    pub assignments: Vec<Assignment>,
+    // number of lowest assignments dropped in each group
+    pub drop_lowest: HashMap<String, usize>,
+    // gradebook csv name
+    pub output: String,
+    // layout of gradebook csv
+    pub format: results::Format,
+    pub keys: KeyMap,
+    // problems of config file, shown in status
+    pub errors: Vec<String>,
+}
+
+impl GradebookConfig {
+    pub fn new(args: &ArgMatches) -> GradebookConfig {
+        let mut errors = Vec::new();
+        let path = args.value_of("config").unwrap();
+        let mut settings = config::Config::default();
+        if let Err(err) = settings.merge(config::File::with_name(path)) {
+            errors.push(format!("Failed to read {}: {}\n", path, err));
+        }
+
+        let mut assignments = Vec::new();
+        let values = match settings.get_array("assignments") {
+            Ok(values) => values,
+            Err(err) => {
+                errors.push(format!("Failed to read assignments in {}: {}\n", path, err));
+                Vec::new()
+            }
+        };
+        for (position, value) in values.into_iter().enumerate() {
+            // malformed assignments are reported and skipped
+            match Self::assignment(value) {
+                Ok(assignment) => assignments.push(assignment),
+                Err(err) => errors.push(format!(
+                    "Skipping assignment {} in {}: {}\n",
+                    position + 1,
+                    path,
+                    err
+                )),
+            }
+        }
+
+        let mut drop_lowest = HashMap::new();
+        if let Ok(table) = settings.get_table("drop_lowest") {
+            for (group, value) in table.into_iter() {
+                match value.into_int() {
+                    Ok(count) if count >= 0 => {
+                        drop_lowest.insert(group, count as usize);
+                    }
+                    _ => errors.push(format!(
+                        "Invalid drop_lowest of {} in {}, skipped\n",
+                        group, path
+                    )),
+                }
+            }
+        }
+
+        let output = match args.value_of("output") {
+            Some(output) => output.to_string(),
+            None => settings
+                .get_str("output")
+                .unwrap_or_else(|_| "gradebook.csv".to_string()),
+        };
+
+        let mut format = configs::result_format(&settings);
+        // percentages are rounded unless set otherwise
+        if settings.get_int("result_csv.precision").is_err() {
+            format.precision = Some(2);
+        }
+        let keys = configs::key_map(&settings);
+        errors.extend(keys.errors.iter().cloned());
+
+        GradebookConfig {
+            assignments,
+            drop_lowest,
+            output,
+            format,
+            keys,
+            errors,
+        }
+    }
+
+    // an [[assignments]] table, Err if name or result is missing or a value has wrong type
+    fn assignment(value: config::Value) -> Result<Assignment, config::ConfigError> {
+        let table = value.into_table()?;
+        let get = |key: &str| {
+            table
+                .get(key)
+                .cloned()
+                .ok_or_else(|| config::ConfigError::NotFound(key.to_string()))
+        };
+        let get_float = |key: &str, default: f64| match table.get(key) {
+            Some(value) => value.clone().into_float(),
+            None => Ok(default),
+        };
+        let mut headers = HashMap::new();
+        if let Some(value) = table.get("headers") {
+            for (name, header) in value.clone().into_table()?.into_iter() {
+                headers.insert(name, header.into_str()?);
+            }
+        }
+        Ok(Assignment {
+            name: get("name")?.into_str()?,
+            results: get("result")?.into_str()?,
+            weight: get_float("weight", 1.0)?,
+            max: get_float("max", 100.0)?,
+            blackbox: get_float("blackbox", 1.0)?,
+            whitebox: get_float("whitebox", 0.0)?,
+            group: match table.get("group") {
+                Some(value) => Some(value.clone().into_str()?),
+                None => None,
+            },
+            format: results::Format {
+                headers,
+                ..results::Format::default()
+            },
+        })
+    }
+}
+
+pub struct Entry {
+    pub student_id: String,
+    pub name: String,
+    pub github: String,
+    // percentage of each assignment, None if not graded
+    pub scores: Vec<Option<f64>>,
+    // whether each assignment is dropped by drop_lowest
+    pub dropped: Vec<bool>,
+    // course total in percentage
+    pub total: f64,
+}
+
+pub struct Gradebook {
+    pub config: GradebookConfig,
+    pub entries: Vec<Entry>,
+    pub status: Vec<String>,
+
+    pub select: Option<usize>,
+    pub render_start: usize,
+}
+
+impl Gradebook {
+    pub fn new(config: GradebookConfig) -> Gradebook {
+        let mut status = config.errors.clone();
+        let mut entries: Vec<Entry> = Vec::new();
+        let count = config.assignments.len();
+
+        for (index, assignment) in config.assignments.iter().enumerate() {
+            if !Path::new(&assignment.results).exists() {
+                status.push(format!(
+                    "Result {} of {} not found\n",
+                    assignment.results, assignment.name
+                ));
+                continue;
+            }
+            let records = results::read(&assignment.results, &assignment.format, &mut status);
+            status.push(format!(
+                "Read {} students from {}\n",
+                records.len(),
+                assignment.name
+            ));
+            for record in records {
+                let score = match (record.blackbox, record.whitebox) {
+                    (None, None) => None,
+                    (blackbox, whitebox) => Some(
+                        (blackbox.unwrap_or(0.0) * assignment.blackbox
+                            + whitebox.unwrap_or(0.0) * assignment.whitebox)
+                            / assignment.max
+                            * 100.0,
+                    ),
+                };
+                let pos = match entries
+                    .iter()
+                    .position(|entry| entry.student_id == record.student_id)
+                {
+                    Some(pos) => pos,
+                    None => {
+                        entries.push(Entry {
+                            student_id: record.student_id,
+                            name: record.name,
+                            github: record.github,
+                            scores: vec![None; count],
+                            dropped: vec![false; count],
+                            total: 0.0,
+                        });
+                        entries.len() - 1
+                    }
+                };
+                entries[pos].scores[index] = score;
+            }
+        }
+
+        for entry in entries.iter_mut() {
+            Self::compute(&config, entry);
+        }
+
+        Gradebook {
+            config,
+            entries,
+            status,
+            select: None,
+            render_start: 0,
+        }
+    }
+
+    // apply drop_lowest and weights of config to a student
+    fn compute(config: &GradebookConfig, entry: &mut Entry) {
+        // ungrouped assignments are never dropped
+        let mut groups: HashMap<Option<&String>, Vec<usize>> = HashMap::new();
+        for (index, assignment) in config.assignments.iter().enumerate() {
+            groups
+                .entry(assignment.group.as_ref())
+                .or_default()
+                .push(index);
+        }
+
+        let mut total = 0.0;
+        let mut total_weight = 0.0;
+        for (group, mut indices) in groups.into_iter() {
+            let group_weight: f64 = indices
+                .iter()
+                .map(|index| config.assignments[*index].weight)
+                .sum();
+            total_weight += group_weight;
+
+            let drop = group
+                .and_then(|group| config.drop_lowest.get(group))
+                .cloned()
+                .unwrap_or(0);
+            // missing grades count as zero and go first
+            indices.sort_by(|a, b| {
+                let a = entry.scores[*a].unwrap_or(0.0);
+                let b = entry.scores[*b].unwrap_or(0.0);
+                a.total_cmp(&b)
+            });
+            let drop = std::cmp::min(drop, indices.len().saturating_sub(1));
+            for index in indices.iter().take(drop) {
+                entry.dropped[*index] = true;
+            }
+
+            let kept = &indices[drop..];
+            let kept_weight: f64 = kept
+                .iter()
+                .map(|index| config.assignments[*index].weight)
+                .sum();
+            if kept_weight > 0.0 {
+                let score: f64 = kept
+                    .iter()
+                    .map(|index| {
+                        config.assignments[*index].weight * entry.scores[*index].unwrap_or(0.0)
+                    })
+                    .sum();
+                total += score / kept_weight * group_weight;
+            }
+        }
+
+        entry.total = if total_weight > 0.0 {
+            total / total_weight
+        } else {
+            0.0
+        };
+    }
+
+    fn gen_results(&self) -> Vec<u8> {
+        let format = &self.config.format;
+        let mut buffer = Vec::new();
+        if format.bom {
+            // UTF-8 BOM
+            buffer.extend_from_slice(&[0xef, 0xbb, 0xbf]);
+        }
+        let mut wtr = csv::WriterBuilder::new()
+            .delimiter(format.delimiter)
+            .from_writer(&mut buffer);
+        let mut header: Vec<String> = (0..3)
+            .map(|index| format.header(&Column::Field(index), &[]))
+            .collect();
+        for assignment in self.config.assignments.iter() {
+            header.push(assignment.name.clone());
+        }
+        header.push(format.header(&Column::Total, &[]));
+        wtr.write_record(&header).unwrap();
+        for entry in self.entries.iter() {
+            let mut record = vec![
+                entry.student_id.clone(),
+                entry.name.clone(),
+                entry.github.clone(),
+            ];
+            for (score, dropped) in entry.scores.iter().zip(entry.dropped.iter()) {
+                record.push(match score {
+                    Some(score) if *dropped => format!("({})", format.grade(Some(*score))),
+                    score => format.grade(*score),
+                });
+            }
+            record.push(format.grade(Some(entry.total)));
+            wtr.write_record(&record).unwrap();
+        }
+        wtr.flush().unwrap();
+        drop(wtr);
+        buffer
+    }
+
+    pub fn save(&mut self) {
+        let buffer = self.gen_results();
+
+        match results::write_atomic(&self.config.output, &buffer) {
+            Ok(()) => self
+                .status
+                .push(format!("Saved to {}\n", self.config.output)),
+            Err(err) => self
+                .status
+                .push(format!("Failed to save {}: {}\n", self.config.output, err)),
+        }
+    }
+
+    pub fn handle(&mut self, event: KeyEvent) -> bool {
+        match self.config.keys.action(&event) {
+            Some(Action::Down) => {
+                self.select = match self.select {
+                    None if !self.entries.is_empty() => Some(0),
+                    Some(current) if current + 1 < self.entries.len() => Some(current + 1),
+                    Some(_) => Some(0),
+                    None => None,
+                };
+            }
+            Some(Action::Up) => {
+                self.select = match self.select {
+                    None if !self.entries.is_empty() => Some(self.entries.len() - 1),
+                    Some(current) if current > 0 => Some(current - 1),
+                    Some(_) => Some(self.entries.len() - 1),
+                    None => None,
+                };
+            }
+            Some(Action::Save) => {
+                self.save();
+            }
+            Some(Action::Quit) => {
+                return true;
+            }
+            _ => {
+                self.status
+                    .push(format!("Unhandled key {:?}\n", event.code));
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,290 @@
+use crate::configs::Config;
+use crate::{results, roster};
+use clap::ArgMatches;
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::Path;
+
+// A student from GitHub Classroom exports
+struct Entry {
+    identifier: String,
+    github: String,
+    github_id: String,
+    name: String,
+}
+
+// read csv by header names, missing columns are empty
+fn read_csv(path: &str, columns: &[&str]) -> csv::Result<Vec<Vec<String>>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(File::open(path)?);
+    let headers: Vec<String> = rdr
+        .headers()?
+        .iter()
+        .map(|header| header.trim_start_matches('\u{feff}').trim().to_string())
+        .collect();
+    let indices: Vec<Option<usize>> = columns
+        .iter()
+        .map(|column| headers.iter().position(|header| header == column))
+        .collect();
+    let mut rows = Vec::new();
+    for row in rdr.records() {
+        let record = row?;
+        rows.push(
+            indices
+                .iter()
+                .map(|index| {
+                    index
+                        .and_then(|index| record.get(index))
+                        .unwrap_or("")
+                        .trim()
+                        .to_string()
+                })
+                .collect(),
+        );
+    }
+    Ok(rows)
+}
+
+// headers and rows of students csv as is, keeping all columns
+fn read_students(path: &str) -> csv::Result<(Vec<String>, Vec<Vec<String>>)> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(File::open(path)?);
+    let headers = rdr
+        .headers()?
+        .iter()
+        .map(|header| header.trim_start_matches('\u{feff}').trim().to_string())
+        .collect();
+    let mut rows = Vec::new();
+    for row in rdr.records() {
+        rows.push(row?.iter().map(String::from).collect());
+    }
+    Ok((headers, rows))
+}
+
+fn write_students(path: &str, headers: &[String], rows: &[Vec<String>]) -> csv::Result<()> {
+    let mut buffer = Vec::new();
+    let mut wtr = csv::Writer::from_writer(&mut buffer);
+    wtr.write_record(headers)?;
+    for row in rows.iter() {
+        wtr.write_record(row)?;
+    }
+    wtr.flush()?;
+    drop(wtr);
+    results::write_atomic(path, &buffer)?;
+    Ok(())
+}
+
+// prints report, Err if a file could not be read or written
+pub fn run(config: &Config, args: &ArgMatches) -> Result<(), String> {
+    let mut report = Vec::new();
+    let result = import(config, args, &mut report);
+    for line in report.iter() {
+        print!("{}", line);
+    }
+    result
+}
+
+fn import(config: &Config, args: &ArgMatches, report: &mut Vec<String>) -> Result<(), String> {
+    let mut headers: Vec<String> = Vec::new();
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    if Path::new(&config.students).exists() {
+        let (read_headers, read_rows) = read_students(&config.students)
+            .map_err(|err| format!("Failed to read {}: {}", config.students, err))?;
+        headers = read_headers;
+        rows = read_rows;
+    } else {
+        let columns = &config.columns;
+        for (name, default) in [
+            (&columns.student_id, "student_id"),
+            (&columns.name, "name"),
+            (&columns.github, "github"),
+        ]
+        .iter()
+        {
+            headers.push((*name).clone().unwrap_or_else(|| default.to_string()));
+        }
+    }
+
+    let columns = &config.columns;
+    let student_id = roster::find_column(&headers, &columns.student_id, 0, report);
+    let name = roster::find_column(&headers, &columns.name, 1, report);
+    let github = roster::find_column(&headers, &columns.github, 2, report);
+    let (student_id, name, github) = match (student_id, name, github) {
+        (Some(student_id), Some(name), Some(github)) => (student_id, name, github),
+        _ => {
+            return Err(format!(
+                "Columns of students not found in {}",
+                config.students
+            ))
+        }
+    };
+
+    let mut entries: Vec<Entry> = Vec::new();
+    let roster_path = args.value_of("roster");
+    if let Some(path) = roster_path {
+        let rows = read_csv(
+            path,
+            &["identifier", "github_username", "github_id", "name"],
+        )
+        .map_err(|err| format!("Failed to read {}: {}", path, err))?;
+        for row in rows {
+            entries.push(Entry {
+                identifier: row[0].clone(),
+                github: row[1].clone(),
+                github_id: row[2].clone(),
+                name: row[3].clone(),
+            });
+        }
+        report.push(format!("Read {} students from {}\n", entries.len(), path));
+    }
+
+    // github usernames who accepted the assignment
+    let mut accepted = HashSet::new();
+    let assignment_path = args.value_of("assignment");
+    if let Some(path) = assignment_path {
+        let rows = read_csv(
+            path,
+            &[
+                "roster_identifier",
+                "github_username",
+                "student_repository_name",
+            ],
+        )
+        .map_err(|err| format!("Failed to read {}: {}", path, err))?;
+        report.push(format!("Read {} submissions from {}\n", rows.len(), path));
+        for row in rows {
+            if row[1].is_empty() {
+                continue;
+            }
+            if !row[2].is_empty() {
+                accepted.insert(row[1].clone());
+            }
+            if row[0].is_empty() {
+                report.push(format!(
+                    "GitHub user {} is not linked to any roster identifier\n",
+                    row[1]
+                ));
+            } else if roster_path.is_none()
+                && !entries.iter().any(|entry| entry.identifier == row[0])
+            {
+                // without roster, the assignment export is the mapping
+                entries.push(Entry {
+                    identifier: row[0].clone(),
+                    github: row[1].clone(),
+                    github_id: String::new(),
+                    name: String::new(),
+                });
+            }
+        }
+    }
+
+    // keep github id to follow renamed GitHub accounts
+    let github_id = if entries.iter().any(|entry| !entry.github_id.is_empty()) {
+        Some(
+            headers
+                .iter()
+                .position(|header| header == "github_id")
+                .unwrap_or_else(|| {
+                    headers.push("github_id".to_string());
+                    headers.len() - 1
+                }),
+        )
+    } else {
+        None
+    };
+    for row in rows.iter_mut() {
+        row.resize(headers.len(), String::new());
+    }
+
+    let mut seen = HashSet::new();
+    for entry in entries.iter() {
+        if entry.identifier.is_empty() {
+            continue;
+        }
+        seen.insert(entry.identifier.clone());
+
+        let row = match rows
+            .iter()
+            .position(|row| row[student_id] == entry.identifier)
+        {
+            Some(pos) => &mut rows[pos],
+            None => {
+                let mut row = vec![String::new(); headers.len()];
+                row[student_id] = entry.identifier.clone();
+                row[name] = entry.name.clone();
+                rows.push(row);
+                report.push(format!("Added {}\n", entry.identifier));
+                rows.last_mut().unwrap()
+            }
+        };
+
+        if entry.github.is_empty() {
+            if row[github].is_empty() {
+                report.push(format!(
+                    "{} {} has not linked a GitHub account\n",
+                    entry.identifier, row[name]
+                ));
+            } else {
+                report.push(format!(
+                    "{} {} is unlinked in classroom, keeping GitHub {}\n",
+                    entry.identifier, row[name], row[github]
+                ));
+            }
+        } else if row[github] != entry.github {
+            if !row[github].is_empty() {
+                report.push(format!(
+                    "GitHub of {} changed from {} to {}\n",
+                    entry.identifier, row[github], entry.github
+                ));
+            }
+            row[github] = entry.github.clone();
+        }
+        if row[name].is_empty() {
+            row[name] = entry.name.clone();
+        } else if !entry.name.is_empty() && row[name] != entry.name {
+            report.push(format!(
+                "Name of {} is {} in roster, keeping {}\n",
+                entry.identifier, entry.name, row[name]
+            ));
+        }
+        if let Some(github_id) = github_id {
+            if !entry.github_id.is_empty() {
+                row[github_id] = entry.github_id.clone();
+            }
+        }
+
+        if assignment_path.is_some()
+            && !entry.github.is_empty()
+            && !accepted.contains(&entry.github)
+        {
+            report.push(format!(
+                "{} {} has not accepted the assignment\n",
+                entry.identifier, row[name]
+            ));
+        }
+    }
+
+    if roster_path.is_some() {
+        for row in rows.iter() {
+            if !seen.contains(&row[student_id]) {
+                report.push(format!(
+                    "{} {} is not in classroom roster\n",
+                    row[student_id], row[name]
+                ));
+            }
+        }
+    }
+
+    if !args.is_present("dry_run") {
+        write_students(&config.students, &headers, &rows)
+            .map_err(|err| format!("Failed to save {}: {}", config.students, err))?;
+        report.push(format!(
+            "Saved {} students to {}\n",
+            rows.len(),
+            config.students
+        ));
+    }
+    Ok(())
+}
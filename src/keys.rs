@@ -0,0 +1,566 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    FocusLeft,
+    FocusDown,
+    FocusUp,
+    FocusRight,
+    Down,
+    Up,
+    Help,
+    Diff,
+    Save,
+    Blackbox,
+    Whitebox,
+    Repeat,
+    Fetch,
+    FetchAll,
+    Grade,
+    GradeAll,
+    Template,
+    Comment,
+    Migrate,
+    Search,
+    Filter,
+    ClearFilter,
+    Sort,
+    SortReverse,
+    Command,
+    Undo,
+    Redo,
+    History,
+    NextAttention,
+    PrevAttention,
+    Quit,
+}
+
+// all actions
+pub const ACTIONS: [Action; 31] = [
+    Action::FocusLeft,
+    Action::FocusDown,
+    Action::FocusUp,
+    Action::FocusRight,
+    Action::Down,
+    Action::Up,
+    Action::Fetch,
+    Action::FetchAll,
+    Action::Template,
+    Action::Grade,
+    Action::GradeAll,
+    Action::Blackbox,
+    Action::Whitebox,
+    Action::Repeat,
+    Action::Comment,
+    Action::Undo,
+    Action::Redo,
+    Action::History,
+    Action::Save,
+    Action::Diff,
+    Action::Migrate,
+    Action::Search,
+    Action::Filter,
+    Action::ClearFilter,
+    Action::Sort,
+    Action::SortReverse,
+    Action::Command,
+    Action::NextAttention,
+    Action::PrevAttention,
+    Action::Help,
+    Action::Quit,
+];
+
+// actions in help popup, grouped by the panel they work in
+const HELP_GROUPS: [(&str, &[Action]); 5] = [
+    (
+        "Global",
+        &[
+            Action::FocusLeft,
+            Action::FocusDown,
+            Action::FocusUp,
+            Action::FocusRight,
+            Action::FetchAll,
+            Action::GradeAll,
+            Action::Template,
+            Action::Save,
+            Action::Diff,
+            Action::Migrate,
+            Action::Command,
+            Action::Help,
+            Action::Quit,
+        ],
+    ),
+    (
+        "Students",
+        &[
+            Action::Down,
+            Action::Up,
+            Action::Fetch,
+            Action::Grade,
+            Action::Blackbox,
+            Action::Whitebox,
+            Action::Repeat,
+            Action::Comment,
+            Action::Undo,
+            Action::Redo,
+            Action::History,
+            Action::NextAttention,
+            Action::PrevAttention,
+        ],
+    ),
+    (
+        "Students: search, filter and sort",
+        &[
+            Action::Search,
+            Action::Filter,
+            Action::ClearFilter,
+            Action::Sort,
+            Action::SortReverse,
+        ],
+    ),
+    ("Rubric", &[Action::Down, Action::Up, Action::Whitebox]),
+    ("Status, Log and Diff", &[Action::Down, Action::Up]),
+];
+
+impl Action {
+    // key in [keys] section of config
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::FocusLeft => "focus_left",
+            Action::FocusDown => "focus_down",
+            Action::FocusUp => "focus_up",
+            Action::FocusRight => "focus_right",
+            Action::Down => "down",
+            Action::Up => "up",
+            Action::Help => "help",
+            Action::Diff => "diff",
+            Action::Save => "save",
+            Action::Blackbox => "blackbox",
+            Action::Whitebox => "whitebox",
+            Action::Repeat => "repeat",
+            Action::Fetch => "fetch",
+            Action::FetchAll => "fetch_all",
+            Action::Grade => "grade",
+            Action::GradeAll => "grade_all",
+            Action::Template => "template",
+            Action::Comment => "comment",
+            Action::Migrate => "migrate",
+            Action::Search => "search",
+            Action::Filter => "filter",
+            Action::ClearFilter => "clear_filter",
+            Action::Sort => "sort",
+            Action::SortReverse => "sort_reverse",
+            Action::Command => "command",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::History => "history",
+            Action::NextAttention => "next_attention",
+            Action::PrevAttention => "prev_attention",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::FocusLeft => "focus panel on the left",
+            Action::FocusDown => "focus panel below",
+            Action::FocusUp => "focus panel above",
+            Action::FocusRight => "focus panel on the right",
+            Action::Down => "select next student/scroll down",
+            Action::Up => "select previous student/scroll up",
+            Action::Help => "show help",
+            Action::Diff => "diff results with saved file",
+            Action::Save => "save results",
+            Action::Blackbox => "[num]+key: set blackbox grade manually",
+            Action::Whitebox => {
+                "[num]+key: set whitebox grade manually, or score of criterion in Rubric"
+            }
+            Action::Repeat => "repeat last grade for current student",
+            Action::Fetch => "fetch selected student",
+            Action::FetchAll => "fetch all students",
+            Action::Grade => "grade blackbox for selected student",
+            Action::GradeAll => "grade blackbox for all students",
+            Action::Template => "bump template repo to newest version",
+            Action::Comment => "edit comment",
+            Action::Migrate => "migrate grades of renamed GitHub accounts",
+            Action::Search => "search students",
+            Action::Filter => "[num]+key: cycle filters/show grade below num",
+            Action::ClearFilter => "clear search and filter",
+            Action::Sort => "cycle sort column",
+            Action::SortReverse => "toggle descending order",
+            Action::Command => "enter command, see :help",
+            Action::Undo => "undo last grade or comment change",
+            Action::Redo => "redo last undone change",
+            Action::History => "show grade history of selected student",
+            Action::NextAttention => "jump to next student needing attention",
+            Action::PrevAttention => "jump to previous student needing attention",
+            Action::Quit => "quit",
+        }
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::FocusLeft => &["H"],
+            Action::FocusDown => &["J"],
+            Action::FocusUp => &["K"],
+            Action::FocusRight => &["L"],
+            Action::Down => &["j"],
+            Action::Up => &["k"],
+            Action::Help => &["h", "?"],
+            Action::Diff => &["d"],
+            Action::Save => &["s"],
+            Action::Blackbox => &["b"],
+            Action::Whitebox => &["w"],
+            Action::Repeat => &["r"],
+            Action::Fetch => &["f"],
+            Action::FetchAll => &["F"],
+            Action::Grade => &["g"],
+            Action::GradeAll => &["G"],
+            Action::Template => &["t"],
+            Action::Comment => &["c"],
+            Action::Migrate => &["M"],
+            Action::Search => &["/"],
+            Action::Filter => &["v"],
+            Action::ClearFilter => &["esc"],
+            Action::Sort => &["o"],
+            Action::SortReverse => &["O"],
+            Action::Command => &[":"],
+            Action::Undo => &["u"],
+            Action::Redo => &["ctrl-r"],
+            Action::History => &["a"],
+            Action::NextAttention => &["n"],
+            Action::PrevAttention => &["N"],
+            Action::Quit => &["q"],
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    // parse keys like "q", "G", "ctrl-s", "alt-enter", "f5"
+    pub fn parse(s: &str) -> Option<Key> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            let lower = rest.to_lowercase();
+            if lower.starts_with("ctrl-") {
+                modifiers |= KeyModifiers::CONTROL;
+            } else if lower.starts_with("alt-") {
+                modifiers |= KeyModifiers::ALT;
+            } else if lower.starts_with("shift-") {
+                modifiers |= KeyModifiers::SHIFT;
+            } else {
+                break;
+            }
+            rest = &rest[rest.find('-').unwrap() + 1..];
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(ch), None) => KeyCode::Char(ch),
+            _ => match rest.to_lowercase().as_str() {
+                "esc" => KeyCode::Esc,
+                "enter" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "space" => KeyCode::Char(' '),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                name if name.starts_with('f') => KeyCode::F(name[1..].parse().ok()?),
+                _ => return None,
+            },
+        };
+        Some(Key { code, modifiers }.normalize())
+    }
+
+    // shift is part of the char itself, and of backtab
+    fn normalize(mut self) -> Key {
+        match self.code {
+            KeyCode::Char(ch) => {
+                if self.modifiers.contains(KeyModifiers::SHIFT) {
+                    self.code = KeyCode::Char(ch.to_ascii_uppercase());
+                }
+                self.modifiers.remove(KeyModifiers::SHIFT);
+            }
+            // shift is implied by backtab, some terminals report it and some don't
+            KeyCode::BackTab => self.modifiers.remove(KeyModifiers::SHIFT),
+            KeyCode::Tab if self.modifiers.contains(KeyModifiers::SHIFT) => {
+                self.code = KeyCode::BackTab;
+                self.modifiers.remove(KeyModifiers::SHIFT);
+            }
+            _ => {}
+        }
+        self
+    }
+
+    pub fn from_event(event: &KeyEvent) -> Key {
+        Key {
+            code: event.code,
+            modifiers: event.modifiers,
+        }
+        .normalize()
+    }
+
+    pub fn display(&self) -> String {
+        let mut s = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            s.push_str("ctrl-");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            s.push_str("alt-");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            s.push_str("shift-");
+        }
+        match self.code {
+            KeyCode::Char(' ') => s.push_str("space"),
+            KeyCode::Char(ch) => s.push(ch),
+            KeyCode::F(n) => s.push_str(&format!("f{}", n)),
+            KeyCode::Esc => s.push_str("esc"),
+            KeyCode::Enter => s.push_str("enter"),
+            KeyCode::Tab => s.push_str("tab"),
+            KeyCode::BackTab => s.push_str("backtab"),
+            KeyCode::Backspace => s.push_str("backspace"),
+            KeyCode::Delete => s.push_str("delete"),
+            KeyCode::Insert => s.push_str("insert"),
+            KeyCode::Up => s.push_str("up"),
+            KeyCode::Down => s.push_str("down"),
+            KeyCode::Left => s.push_str("left"),
+            KeyCode::Right => s.push_str("right"),
+            KeyCode::Home => s.push_str("home"),
+            KeyCode::End => s.push_str("end"),
+            KeyCode::PageUp => s.push_str("pageup"),
+            KeyCode::PageDown => s.push_str("pagedown"),
+            code => s.push_str(&format!("{:?}", code)),
+        }
+        s
+    }
+}
+
+#[derive(Clone)]
+pub struct KeyMap {
+    bindings: Vec<(Key, Action)>,
+    // invalid or conflicting bindings in config
+    pub errors: Vec<String>,
+}
+
+impl KeyMap {
+    // keys of actions in config replace the default ones
+    pub fn new(config: &HashMap<String, Vec<String>>) -> KeyMap {
+        let mut bindings: Vec<(Key, Action)> = Vec::new();
+        let mut errors = Vec::new();
+
+        for name in config.keys() {
+            if !ACTIONS.iter().any(|action| action.name() == name) {
+                errors.push(format!("Unknown action {} in key bindings\n", name));
+            }
+        }
+
+        for action in ACTIONS.iter() {
+            let keys: Vec<String> = match config.get(action.name()) {
+                Some(keys) => keys.clone(),
+                None => action
+                    .default_keys()
+                    .iter()
+                    .map(|key| key.to_string())
+                    .collect(),
+            };
+            for name in keys.iter() {
+                let key = match Key::parse(name) {
+                    Some(key) => key,
+                    None => {
+                        errors.push(format!(
+                            "Invalid key {} for action {}\n",
+                            name,
+                            action.name()
+                        ));
+                        continue;
+                    }
+                };
+                if let KeyCode::Char(ch) = key.code {
+                    if key.modifiers.is_empty() && (ch.is_ascii_digit() || ch == '.') {
+                        errors.push(format!(
+                            "Key {} for action {} is reserved for grades\n",
+                            name,
+                            action.name()
+                        ));
+                        continue;
+                    }
+                }
+                if let Some((_, other)) = bindings.iter().find(|(k, _)| *k == key) {
+                    errors.push(format!(
+                        "Key {} for action {} is already bound to {}\n",
+                        name,
+                        action.name(),
+                        other.name()
+                    ));
+                    continue;
+                }
+                bindings.push((key, *action));
+            }
+        }
+
+        KeyMap { bindings, errors }
+    }
+
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        let key = Key::from_event(event);
+        self.bindings
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, action)| *action)
+    }
+
+    // keys bound to action, joined for display
+    pub fn keys(&self, action: Action) -> String {
+        let keys: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(key, _)| key.display())
+            .collect();
+        keys.join(" ")
+    }
+
+    // bindings of groups in help, skipping unbound actions
+    pub fn help(&self) -> Vec<(&'static str, Vec<(String, &'static str)>)> {
+        let mut help = Vec::new();
+        for (group, actions) in HELP_GROUPS.iter() {
+            let mut bindings = Vec::new();
+            for action in actions.iter() {
+                let keys = self.keys(*action);
+                if !keys.is_empty() {
+                    bindings.push((keys, action.description()));
+                }
+            }
+            help.push((*group, bindings));
+        }
+        help
+    }
+
+    // one line hint like "j/k: move  g: grade"
+    pub fn hints(&self, hints: &[(Action, &str)]) -> String {
+        let mut line = Vec::new();
+        for (action, label) in hints.iter() {
+            let keys = self.keys(*action);
+            if let Some(key) = keys.split(' ').next().filter(|key| !key.is_empty()) {
+                line.push(format!("{}: {}", key, label));
+            }
+        }
+        line.join("  ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent { code, modifiers }
+    }
+
+    // display of parsed key, None if invalid
+    fn round_trip(s: &str) -> Option<String> {
+        Key::parse(s).map(|key| key.display())
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(round_trip("q"), Some("q".to_string()));
+        assert_eq!(round_trip("G"), Some("G".to_string()));
+        assert_eq!(round_trip("ctrl-s"), Some("ctrl-s".to_string()));
+        assert_eq!(
+            round_trip("Ctrl-Alt-Enter"),
+            Some("ctrl-alt-enter".to_string())
+        );
+        assert_eq!(round_trip("shift-g"), Some("G".to_string()));
+        assert_eq!(round_trip("space"), Some("space".to_string()));
+        assert_eq!(round_trip("f5"), Some("f5".to_string()));
+        assert_eq!(round_trip("PageDown"), Some("pagedown".to_string()));
+        assert_eq!(round_trip("shift-tab"), Some("backtab".to_string()));
+        assert_eq!(round_trip("shift-up"), Some("shift-up".to_string()));
+        assert_eq!(round_trip("fx"), None);
+        assert_eq!(round_trip("enterr"), None);
+        assert_eq!(round_trip(""), None);
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for name in [
+            "q",
+            "G",
+            "?",
+            "ctrl-c",
+            "alt-enter",
+            "shift-left",
+            "esc",
+            "tab",
+            "backtab",
+            "backspace",
+            "delete",
+            "insert",
+            "home",
+            "end",
+            "pageup",
+            "f12",
+            "space",
+        ]
+        .iter()
+        {
+            let key = Key::parse(name).unwrap();
+            assert!(Key::parse(&key.display()).unwrap() == key, "{}", name);
+            assert_eq!(key.display(), *name);
+        }
+    }
+
+    #[test]
+    fn from_event() {
+        let shifted = Key::from_event(&event(KeyCode::Char('g'), KeyModifiers::SHIFT));
+        assert!(shifted == Key::parse("G").unwrap());
+        // terminals report backtab with or without shift
+        let backtab = Key::parse("backtab").unwrap();
+        assert!(Key::from_event(&event(KeyCode::BackTab, KeyModifiers::SHIFT)) == backtab);
+        assert!(Key::from_event(&event(KeyCode::BackTab, KeyModifiers::NONE)) == backtab);
+    }
+
+    #[test]
+    fn key_map() {
+        let mut config = HashMap::new();
+        config.insert("save".to_string(), vec!["ctrl-s".to_string()]);
+        config.insert("quit".to_string(), vec!["1".to_string(), "j".to_string()]);
+        config.insert("jump".to_string(), vec!["x".to_string()]);
+        let keys = KeyMap::new(&config);
+        assert_eq!(keys.keys(Action::Save), "ctrl-s");
+        assert_eq!(
+            keys.action(&event(KeyCode::Char('s'), KeyModifiers::CONTROL)),
+            Some(Action::Save)
+        );
+        assert_eq!(
+            keys.action(&event(KeyCode::Char('s'), KeyModifiers::NONE)),
+            None
+        );
+        assert_eq!(
+            keys.errors,
+            vec![
+                "Unknown action jump in key bindings\n".to_string(),
+                "Key 1 for action quit is reserved for grades\n".to_string(),
+                "Key j for action quit is already bound to down\n".to_string(),
+            ]
+        );
+    }
+}
@@ -2,54 +2,182 @@
 extern crate clap;
 extern crate config;
 
-use crossterm::terminal::{enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
 use crossterm::ExecutableCommand;
 use std::io;
 use tui::backend::CrosstermBackend;
+use tui::layout::Rect;
 use tui::Terminal;
 
+mod audit;
+mod command;
 mod configs;
+mod editor;
 mod events;
 mod execute;
+mod formula;
+mod gradebook;
+mod import;
+mod keys;
+mod merge;
 mod model;
+mod results;
+mod roster;
+mod rubric;
+mod session;
+mod snippets;
+mod tas;
 mod view;
 
-fn main() -> Result<(), io::Error> {
-    let config = configs::Config::new();
-
-    // setup term
+fn setup_terminal() -> Result<Terminal<CrosstermBackend<io::Stdout>>, io::Error> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     stdout.execute(EnterAlternateScreen)?;
+    stdout.execute(EnableMouseCapture)?;
 
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     terminal.hide_cursor()?;
     terminal.clear()?;
+    Ok(terminal)
+}
+
+fn restore_terminal() -> Result<(), io::Error> {
+    // leave alternate screen to clear
+    let mut stdout = io::stdout();
+    stdout.execute(DisableMouseCapture)?;
+    stdout.execute(LeaveAlternateScreen)?;
+    Ok(())
+}
+
+// run $EDITOR on the whole terminal, then take it back
+fn edit_externally(
+    model: &mut model::Model,
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    events: &events::Events,
+) -> Result<(), io::Error> {
+    events.pause();
+    disable_raw_mode()?;
+    restore_terminal()?;
+    terminal.show_cursor()?;
+
+    model.run_editor();
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    stdout.execute(EnterAlternateScreen)?;
+    stdout.execute(EnableMouseCapture)?;
+    terminal.clear()?;
+    events.resume();
+    Ok(())
+}
+
+fn run_gradebook(args: &clap::ArgMatches) -> Result<(), io::Error> {
+    let config = gradebook::GradebookConfig::new(args);
+    let mut gradebook = gradebook::Gradebook::new(config);
+
+    if args.is_present("export") {
+        gradebook.save();
+        for line in gradebook.status.iter() {
+            print!("{}", line);
+        }
+        return Ok(());
+    }
+
+    let mut terminal = setup_terminal()?;
+    let events = events::Events::new();
+
+    loop {
+        terminal.draw(|f| {
+            view::draw_gradebook(&mut gradebook, f);
+        })?;
+
+        match events.next().unwrap() {
+            events::Event::Input(key) => {
+                if gradebook.handle(key) {
+                    break;
+                }
+            }
+            events::Event::Resize(columns, rows) => {
+                terminal.resize(Rect::new(0, 0, columns, rows))?;
+            }
+            events::Event::Mouse(_) | events::Event::Tick => {}
+        }
+    }
+
+    restore_terminal()
+}
+
+fn main() -> Result<(), io::Error> {
+    let args = configs::args();
+    if let Some(args) = args.subcommand_matches("gradebook") {
+        return run_gradebook(args);
+    }
+    if let Some(args) = args.subcommand_matches("assign") {
+        let config = configs::Config::new(args);
+        tas::run(&config, args);
+        return Ok(());
+    }
+    if let Some(args) = args.subcommand_matches("merge") {
+        if let Err(err) = merge::run(args) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(args) = args.subcommand_matches("import") {
+        let config = configs::Config::new(args);
+        if let Err(err) = import::run(&config, args) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let config = configs::Config::new(&args);
+    if let Some(ta) = &config.ta {
+        if let Err(err) = tas::check(&config.tas, ta) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+
+    // setup term
+    let mut terminal = setup_terminal()?;
 
     let events = events::Events::new();
     let mut model = model::Model::new(config);
 
     loop {
-        terminal.draw(|mut f| {
-            view::draw(&mut model, &mut f);
+        terminal.draw(|f| {
+            view::draw(&mut model, f);
         })?;
 
         match events.next().unwrap() {
             events::Event::Input(key) => {
-                if model.handle(key.code) {
+                if model.handle(key) {
                     break;
                 }
+                if model.edit_externally {
+                    edit_externally(&mut model, &mut terminal, &events)?;
+                }
             }
-            _ => {}
+            events::Event::Mouse(event) => {
+                model.handle_mouse(event);
+            }
+            events::Event::Resize(columns, rows) => {
+                // re-layout on next draw right away
+                terminal.resize(Rect::new(0, 0, columns, rows))?;
+            }
+            events::Event::Tick => {}
         }
 
         model.tick();
     }
 
-    // leave alternate screen to clear
-    let mut stdout = io::stdout();
-    stdout.execute(LeaveAlternateScreen)?;
-
-    Ok(())
+    model.save_session();
+    restore_terminal()
 }
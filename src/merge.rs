@@ -0,0 +1,431 @@
+use crate::{audit, results};
+use chrono::{DateTime, Utc};
+use clap::ArgMatches;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::time::SystemTime;
+
+// student id, name and GitHub, found by header and taken from the first file
+const IDENTITY: usize = 3;
+
+// rows of a student with index of their file
+type Rows<'a> = Vec<(usize, &'a Vec<String>)>;
+
+// a results csv of one TA
+struct Input {
+    path: String,
+    modified: DateTime<Utc>,
+    // last change of student id and field in audit log of the file
+    changes: HashMap<(String, String), (DateTime<Utc>, String)>,
+    delimiter: u8,
+    headers: Vec<String>,
+    // positions of known fields, student id to comment
+    fields: Vec<Option<usize>>,
+    rows: Vec<Vec<String>>,
+}
+
+fn read(path: &str) -> csv::Result<Input> {
+    let text = fs::read_to_string(path)?;
+    let delimiter = results::sniff_delimiter(&text, b',');
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let headers: Vec<String> = rdr
+        .headers()?
+        .iter()
+        .map(|header| header.trim_start_matches('\u{feff}').to_string())
+        .collect();
+    let fields = results::Format::default().positions(&headers);
+    let mut rows = Vec::new();
+    for row in rdr.records() {
+        rows.push(row?.iter().map(String::from).collect());
+    }
+    let modified = fs::metadata(path)?
+        .modified()
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+        .into();
+    Ok(Input {
+        path: path.to_string(),
+        modified,
+        changes: audit::last_changes(&audit::path(path)),
+        delimiter,
+        headers,
+        fields,
+        rows,
+    })
+}
+
+fn is_empty(value: &str, missing: &str) -> bool {
+    value.is_empty() || value == missing
+}
+
+// grades written differently, e.g. 80 and 80.0, are the same
+fn same(a: &str, b: &str) -> bool {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+// index into conflicting values picked by rules, None if still conflicting
+fn resolve(
+    values: &[&str],
+    changed: &[DateTime<Utc>],
+    rules: &[&str],
+    missing: &str,
+) -> Option<usize> {
+    for rule in rules.iter() {
+        if *rule == "latest" {
+            return (0..values.len())
+                .filter(|&index| !is_empty(values[index], missing))
+                .max_by_key(|&index| changed[index]);
+        }
+    }
+    None
+}
+
+fn ask(
+    student: &str,
+    column: &str,
+    values: &[(usize, &str)],
+    inputs: &[Input],
+) -> io::Result<usize> {
+    println!("{} {}:", student, column);
+    for (index, (input, value)) in values.iter().enumerate() {
+        println!(
+            "  {}) {} ({})",
+            index + 1,
+            value.replace('\n', " ↵ "),
+            inputs[*input].path
+        );
+    }
+    let stdin = io::stdin();
+    loop {
+        print!("Choose 1-{}: ", values.len());
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no choice"));
+        }
+        if let Ok(choice) = line.trim().parse::<usize>() {
+            if choice >= 1 && choice <= values.len() {
+                return Ok(choice - 1);
+            }
+        }
+    }
+}
+
+// log grades and comments of merged file as imported from the file each was taken from,
+// other columns such as rubric scores are logged when the merged file is loaded in tui
+fn log(
+    output: &str,
+    columns: &[(String, Option<usize>)],
+    merged: &[Vec<(String, Option<usize>)>],
+    inputs: &[Input],
+    missing: &str,
+) -> csv::Result<()> {
+    let path = audit::path(output);
+    let last = audit::last_changes(&path);
+    let id = columns.iter().position(|(_, field)| *field == Some(0));
+    for record in merged.iter() {
+        let student_id = match id {
+            Some(id) => &record[id].0,
+            None => return Ok(()),
+        };
+        // changes of each file the student has values from
+        let mut changes: Vec<(usize, Vec<[String; 3]>)> = Vec::new();
+        for ((_, field), (value, input)) in columns.iter().zip(record.iter()) {
+            let (field, input) = match (field, input) {
+                (Some(field), Some(input)) if *field >= IDENTITY => {
+                    (results::FIELDS[*field], *input)
+                }
+                _ => continue,
+            };
+            // written like grades in audit log of tui
+            let value = match value.parse::<f64>() {
+                Ok(grade) => grade.to_string(),
+                Err(_) if is_empty(value, missing) && field != "comment" => "N/A".to_string(),
+                Err(_) => value.clone(),
+            };
+            let old = match last.get(&(student_id.clone(), field.to_string())) {
+                Some((_, old)) if *old == value => continue,
+                Some((_, old)) => old.clone(),
+                None if field == "comment" && value.is_empty() => continue,
+                None if value == "N/A" => continue,
+                None if field == "comment" => String::new(),
+                None => "N/A".to_string(),
+            };
+            let change = [field.to_string(), old, value];
+            match changes.iter_mut().find(|(index, _)| *index == input) {
+                Some((_, changes)) => changes.push(change),
+                None => changes.push((input, vec![change])),
+            }
+        }
+        for (input, changes) in changes.iter() {
+            let source = audit::Source::Import(inputs[*input].path.clone());
+            audit::log(&path, student_id, changes, &source)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn run(args: &ArgMatches) -> Result<(), String> {
+    let mut report = Vec::new();
+    let rules: Vec<&str> = args
+        .values_of("prefer")
+        .map_or(Vec::new(), Iterator::collect);
+    let dry_run = args.is_present("dry_run");
+    let missing = args.value_of("missing").unwrap();
+
+    let mut inputs = Vec::new();
+    for path in args.values_of("inputs").unwrap() {
+        match read(path) {
+            Ok(input) => {
+                report.push(format!("Read {} results from {}\n", input.rows.len(), path));
+                inputs.push(input);
+            }
+            Err(err) => return Err(format!("Failed to read {}: {}", path, err)),
+        }
+    }
+
+    // columns of all files in order of first appearance, with known field if one
+    let mut columns: Vec<(String, Option<usize>)> = Vec::new();
+    for input in inputs.iter() {
+        for (position, header) in input.headers.iter().enumerate() {
+            let field = input
+                .fields
+                .iter()
+                .position(|field| *field == Some(position));
+            let seen = columns.iter().any(|(name, seen)| match field {
+                Some(_) => *seen == field,
+                None => seen.is_none() && name == header,
+            });
+            if !seen {
+                columns.push((header.clone(), field));
+            }
+        }
+    }
+
+    // rows of each student from every file, in order of first appearance
+    let mut students: Vec<(String, Rows)> = Vec::new();
+    for (index, input) in inputs.iter().enumerate() {
+        for row in input.rows.iter() {
+            let student_id = input.fields[0]
+                .and_then(|position| row.get(position))
+                .cloned()
+                .unwrap_or_default();
+            match students.iter_mut().find(|(id, _)| *id == student_id) {
+                Some((_, rows)) => rows.push((index, row)),
+                None => students.push((student_id, vec![(index, row)])),
+            }
+        }
+    }
+
+    let mut merged = Vec::new();
+    let mut gaps = 0;
+    let mut conflicts = 0;
+    let mut unresolved = 0;
+    for (student_id, rows) in students.iter() {
+        let (first, first_row) = rows[0];
+        let name = inputs[first].fields[1]
+            .and_then(|position| first_row.get(position))
+            .map_or("", String::as_str);
+        let student = format!("{} {}", student_id, name);
+        // values with the file taken from, None if left empty
+        let mut record: Vec<(String, Option<usize>)> = Vec::new();
+        for (header, field) in columns.iter() {
+            // value of this column in every file having it
+            let values: Vec<(usize, &str)> = rows
+                .iter()
+                .filter_map(|(input, row)| {
+                    let fields = &inputs[*input].fields;
+                    let position =
+                        match field {
+                            Some(field) => fields[*field],
+                            None => inputs[*input].headers.iter().enumerate().position(
+                                |(position, h)| h == header && !fields.contains(&Some(position)),
+                            ),
+                        };
+                    position.map(|position| (*input, row.get(position).map_or("", String::as_str)))
+                })
+                .collect();
+            let identity = field.is_some_and(|field| field < IDENTITY);
+            if identity || values.iter().all(|(_, value)| same(value, values[0].1)) {
+                let (input, value) = values
+                    .iter()
+                    .find(|(input, _)| *input == first)
+                    .or_else(|| values.first())
+                    .map_or((None, ""), |(input, value)| (Some(*input), value));
+                record.push((value.to_string(), input));
+                continue;
+            }
+
+            // a value missing in some files only fills the gap
+            let nonempty: Vec<(usize, &str)> = values
+                .iter()
+                .cloned()
+                .filter(|(_, value)| !is_empty(value, missing))
+                .collect();
+            if nonempty.iter().all(|(_, value)| same(value, nonempty[0].1)) {
+                gaps += 1;
+                let (input, value) = nonempty.first().unwrap_or(&values[0]);
+                record.push((value.to_string(), Some(*input)));
+                continue;
+            }
+            conflicts += 1;
+
+            // when each value was last changed, by audit log or else file time
+            let field = match field {
+                Some(field) => results::FIELDS[*field],
+                None => header,
+            };
+            let changed: Vec<DateTime<Utc>> = values
+                .iter()
+                .map(|(input, _)| {
+                    let input = &inputs[*input];
+                    input
+                        .changes
+                        .get(&(student_id.clone(), field.to_string()))
+                        .map_or(input.modified, |(time, _)| *time)
+                })
+                .collect();
+            let strings: Vec<&str> = values.iter().map(|(_, value)| *value).collect();
+            let choice = match resolve(&strings, &changed, &rules, missing) {
+                Some(choice) => Some(choice),
+                None if dry_run => None,
+                None => match ask(&student, header, &values, &inputs) {
+                    Ok(choice) => Some(choice),
+                    Err(err) => return Err(format!("Merge aborted: {}", err)),
+                },
+            };
+            match choice {
+                Some(choice) => {
+                    let (input, value) = values[choice];
+                    report.push(format!(
+                        "{} {}: {} from {}\n",
+                        student,
+                        header,
+                        value.replace('\n', " ↵ "),
+                        inputs[input].path
+                    ));
+                    record.push((value.to_string(), Some(input)));
+                }
+                None => {
+                    unresolved += 1;
+                    let all: Vec<String> = values
+                        .iter()
+                        .map(|(input, value)| {
+                            format!("{} ({})", value.replace('\n', " ↵ "), inputs[*input].path)
+                        })
+                        .collect();
+                    report.push(format!(
+                        "Conflict of {} {}: {}\n",
+                        student,
+                        header,
+                        all.join(", ")
+                    ));
+                    record.push((String::new(), None));
+                }
+            }
+        }
+        merged.push(record);
+    }
+    report.push(format!(
+        "Merged {} students, {} grades missing in some files, {} conflicts, {} unresolved\n",
+        merged.len(),
+        gaps,
+        conflicts,
+        unresolved
+    ));
+
+    let mut saved = Ok(());
+    if !dry_run {
+        let output = args.value_of("output").unwrap();
+        // UTF-8 BOM like results written by tui
+        let mut buffer = vec![0xef, 0xbb, 0xbf];
+        let mut wtr = csv::WriterBuilder::new()
+            .delimiter(inputs[0].delimiter)
+            .from_writer(&mut buffer);
+        wtr.write_record(columns.iter().map(|(header, _)| header))
+            .unwrap();
+        for record in merged.iter() {
+            wtr.write_record(record.iter().map(|(value, _)| value))
+                .unwrap();
+        }
+        wtr.flush().unwrap();
+        drop(wtr);
+        match results::write_atomic(output, &buffer) {
+            Ok(()) => {
+                report.push(format!("Saved to {}\n", output));
+                if let Err(err) = log(output, &columns, &merged, &inputs, missing) {
+                    report.push(format!(
+                        "Failed to write audit log of {}: {}\n",
+                        output, err
+                    ));
+                }
+            }
+            Err(err) => saved = Err(format!("Failed to save {}: {}", output, err)),
+        }
+    }
+
+    for line in report.iter() {
+        print!("{}", line);
+    }
+    saved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // change times in seconds
+    fn times(seconds: &[i64]) -> Vec<DateTime<Utc>> {
+        seconds.iter().map(|s| Utc.timestamp(*s, 0)).collect()
+    }
+
+    #[test]
+    fn latest() {
+        let latest = ["latest"];
+        assert_eq!(
+            resolve(&["80", "90"], &times(&[1, 2]), &latest, "N/A"),
+            Some(1)
+        );
+        assert_eq!(
+            resolve(&["80", "90"], &times(&[2, 1]), &latest, "N/A"),
+            Some(0)
+        );
+        // a value removed later is not taken
+        assert_eq!(
+            resolve(&["80", "N/A", ""], &times(&[1, 2, 3]), &latest, "N/A"),
+            Some(0)
+        );
+        assert_eq!(
+            resolve(&["-", "90"], &times(&[2, 1]), &latest, "-"),
+            Some(1)
+        );
+        assert_eq!(resolve(&["", "N/A"], &times(&[1, 2]), &latest, "N/A"), None);
+    }
+
+    #[test]
+    fn no_rules() {
+        assert_eq!(resolve(&["80", "90"], &times(&[1, 2]), &[], "N/A"), None);
+        assert_eq!(
+            resolve(&["80", "90"], &times(&[1, 2]), &["first"], "N/A"),
+            None
+        );
+    }
+
+    #[test]
+    fn same_values() {
+        assert!(same("80", "80.0"));
+        assert!(same("good", "good"));
+        assert!(!same("80", "80.5"));
+        assert!(!same("Good", "good"));
+        assert!(is_empty("", "N/A"));
+        assert!(is_empty("N/A", "N/A"));
+        assert!(!is_empty("0", "N/A"));
+    }
+}
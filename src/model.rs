@@ -1,19 +1,95 @@
+use crate::audit::{self, Source};
+use crate::command::{self, Format, Selector};
 use crate::configs::Config;
+use crate::editor::Editor;
 use crate::execute;
-use crossterm::event::KeyCode;
+use crate::formula::{self, Expr};
+use crate::keys::Action;
+use crate::results::{self, Column};
+use crate::roster;
+use crate::rubric::{self, Criterion};
+use crate::session::{self, Session};
+use crate::snippets::{self, Snippet};
+use crate::tas;
+use chrono::{DateTime, Utc};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use serde_json::Value;
-use std::fs::File;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::io::Write;
 use std::path::Path;
 use std::process::{Command, Stdio};
 use std::sync::mpsc;
+use std::time::{Duration, Instant};
 use threadpool::ThreadPool;
+use tui::layout::Rect;
 
+#[derive(Clone, Copy, PartialEq)]
 pub enum UiWidget {
     Student,
     Status,
     Log,
     Diff,
+    Rubric,
+}
+
+impl UiWidget {
+    fn name(&self) -> &'static str {
+        match self {
+            UiWidget::Student => "student",
+            UiWidget::Status => "status",
+            UiWidget::Log => "log",
+            UiWidget::Diff => "diff",
+            UiWidget::Rubric => "rubric",
+        }
+    }
+
+    fn parse(name: &str) -> Option<UiWidget> {
+        [
+            UiWidget::Student,
+            UiWidget::Status,
+            UiWidget::Log,
+            UiWidget::Diff,
+            UiWidget::Rubric,
+        ]
+        .iter()
+        .find(|widget| widget.name() == name)
+        .cloned()
+    }
+}
+
+// screen areas of panels in last draw, for mouse events
+#[derive(Default)]
+pub struct Areas {
+    pub student: Rect,
+    pub status: Rect,
+    pub log: Rect,
+    pub diff: Rect,
+    pub rubric: Rect,
+}
+
+impl Areas {
+    fn widget(&self, column: u16, row: u16) -> Option<UiWidget> {
+        let contains = |rect: &Rect| {
+            column >= rect.x
+                && column < rect.x + rect.width
+                && row >= rect.y
+                && row < rect.y + rect.height
+        };
+        if contains(&self.student) {
+            Some(UiWidget::Student)
+        } else if contains(&self.status) {
+            Some(UiWidget::Status)
+        } else if contains(&self.log) {
+            Some(UiWidget::Log)
+        } else if contains(&self.diff) {
+            Some(UiWidget::Diff)
+        } else if contains(&self.rubric) {
+            Some(UiWidget::Rubric)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -23,29 +99,283 @@ pub enum Select {
     Last,
 }
 
+#[derive(Clone)]
 pub struct Student {
     pub student_id: String,
     pub name: String,
     pub github: String,
+    // extra columns of students csv, e.g. section, email
+    pub extra: HashMap<String, String>,
+    // team of group assignment, sharing the team repo
+    pub team: Option<String>,
+    pub blackbox: Option<f64>,
+    pub whitebox: Option<f64>,
+    pub comment: Option<String>,
+    // commit time of repo HEAD
+    pub last_commit: Option<DateTime<Utc>>,
+    // last fetch of repo failed
+    pub fetch_failed: bool,
+    // commit hash of repo HEAD
+    pub head: Option<String>,
+    // commit hash of repo HEAD when last graded
+    pub graded_commit: Option<String>,
+    // last grader run produced no grade
+    pub grader_error: bool,
+    // score of each rubric criterion
+    pub rubric: Vec<Option<f64>>,
+    // points deducted by snippets, subtracted from the sum of rubric scores
+    pub deduction: f64,
+}
+
+impl Student {
+    pub fn grades(&self) -> Grades {
+        Grades {
+            blackbox: self.blackbox,
+            whitebox: self.whitebox,
+            comment: self.comment.clone(),
+            rubric: self.rubric.clone(),
+            deduction: self.deduction,
+        }
+    }
+
+    fn set_grades(&mut self, grades: Grades) {
+        self.blackbox = grades.blackbox;
+        self.whitebox = grades.whitebox;
+        self.comment = grades.comment;
+        self.rubric = grades.rubric;
+        self.deduction = grades.deduction;
+    }
+
+    // repo of this student, or of the team in group assignments
+    pub fn repo(&self, prefix: &str) -> String {
+        if let Some(team) = &self.team {
+            format!("{}-{}", prefix, team)
+        } else {
+            format!("{}-{}", prefix, self.github)
+        }
+    }
+
+    // last commit after deadline
+    pub fn late(&self, deadline: Option<DateTime<Utc>>) -> bool {
+        match (self.last_commit, deadline) {
+            (Some(last_commit), Some(deadline)) => last_commit > deadline,
+            _ => false,
+        }
+    }
+
+    // missing grades, grader failed or repo changed since last graded
+    pub fn needs_attention(&self) -> bool {
+        self.blackbox.is_none()
+            || self.whitebox.is_none()
+            || self.grader_error
+            || (self.graded_commit.is_some() && self.graded_commit != self.head)
+    }
+}
+
+// grades and comment of a student, restored by undo and redo
+#[derive(Clone, Default, PartialEq)]
+pub struct Grades {
     pub blackbox: Option<f64>,
     pub whitebox: Option<f64>,
     pub comment: Option<String>,
+    pub rubric: Vec<Option<f64>>,
+    pub deduction: f64,
+}
+
+impl Grades {
+    // these grades with fields changed from one to another applied,
+    // Err with the field if it was changed since to something else
+    fn apply(&self, from: &Grades, to: &Grades) -> Result<Grades, String> {
+        fn field<T: Clone + PartialEq>(
+            name: &str,
+            current: &T,
+            from: &T,
+            to: &T,
+        ) -> Result<T, String> {
+            if from == to {
+                Ok(current.clone())
+            } else if current == from {
+                Ok(to.clone())
+            } else {
+                Err(name.to_string())
+            }
+        }
+        let mut rubric = Vec::new();
+        for (index, current) in self.rubric.iter().enumerate() {
+            let from = from.rubric.get(index).cloned().flatten();
+            let to = to.rubric.get(index).cloned().flatten();
+            rubric.push(field("rubric score", current, &from, &to)?);
+        }
+        Ok(Grades {
+            blackbox: field("blackbox", &self.blackbox, &from.blackbox, &to.blackbox)?,
+            whitebox: field("whitebox", &self.whitebox, &from.whitebox, &to.whitebox)?,
+            comment: field("comment", &self.comment, &from.comment, &to.comment)?,
+            rubric,
+            deduction: field("deduction", &self.deduction, &from.deduction, &to.deduction)?,
+        })
+    }
+}
+
+// one undoable change to some students
+pub struct Edit {
+    // student, grades before and after
+    pub changes: Vec<(usize, Grades, Grades)>,
+    // selection before and after
+    pub select_before: Option<usize>,
+    pub select_after: Option<usize>,
+}
+
+// hash and commit time of HEAD in the repo
+fn head_commit<P: AsRef<Path>>(repo: P) -> Option<(String, DateTime<Utc>)> {
+    let output = Command::new("git")
+        .current_dir(repo)
+        .arg("log")
+        .arg("-1")
+        .arg("--format=%H %cI")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    let mut parts = output.trim().splitn(2, ' ');
+    let hash = parts.next()?.to_string();
+    let time = DateTime::parse_from_rfc3339(parts.next()?).ok()?;
+    Some((hash, time.with_timezone(&Utc)))
 }
 
 pub enum Message {
     Status(String),
-    Grade((usize, Option<f64>)),
+    // student, grade, graded commit
+    Grade((usize, Option<f64>, Option<String>)),
+    // repo, fetch succeeded
+    Fetch((String, bool)),
+    // repo, hash and commit time of HEAD
+    Commit((String, Option<(String, DateTime<Utc>)>)),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SortKey {
+    // order of students csv
+    Roster,
+    StudentId,
+    Name,
+    GitHub,
+    Blackbox,
+    Whitebox,
+    Late,
+    LastCommit,
+}
+
+impl SortKey {
+    fn next(self) -> SortKey {
+        match self {
+            SortKey::Roster => SortKey::StudentId,
+            SortKey::StudentId => SortKey::Name,
+            SortKey::Name => SortKey::GitHub,
+            SortKey::GitHub => SortKey::Blackbox,
+            SortKey::Blackbox => SortKey::Whitebox,
+            SortKey::Whitebox => SortKey::Late,
+            SortKey::Late => SortKey::LastCommit,
+            SortKey::LastCommit => SortKey::Roster,
+        }
+    }
+
+    fn parse(name: &str) -> Option<SortKey> {
+        let mut sort = SortKey::Roster;
+        loop {
+            if sort.name() == name {
+                return Some(sort);
+            }
+            sort = sort.next();
+            if let SortKey::Roster = sort {
+                return None;
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SortKey::Roster => "roster",
+            SortKey::StudentId => "student id",
+            SortKey::Name => "name",
+            SortKey::GitHub => "GitHub",
+            SortKey::Blackbox => "blackbox",
+            SortKey::Whitebox => "whitebox",
+            SortKey::Late => "late",
+            SortKey::LastCommit => "last commit",
+        }
+    }
+}
+
+pub enum TextInput {
+    Comment,
+    Search,
+    Command,
 }
 
 pub enum InputMode {
     Normal,
-    Text,
+    Text(TextInput),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Filter {
+    All,
+    Ungraded,
+    Late,
+    FetchFailed,
+    Commented,
+    // blackbox grade below
+    Below(f64),
+}
+
+impl Filter {
+    // cycle through filters, Below is only set with a number
+    fn next(self) -> Filter {
+        match self {
+            Filter::All => Filter::Ungraded,
+            Filter::Ungraded => Filter::Late,
+            Filter::Late => Filter::FetchFailed,
+            Filter::FetchFailed => Filter::Commented,
+            Filter::Commented => Filter::All,
+            Filter::Below(_) => Filter::All,
+        }
+    }
+
+    fn parse(name: &str) -> Option<Filter> {
+        if let Some(grade) = name.strip_prefix("below ") {
+            return grade.parse().ok().map(Filter::Below);
+        }
+        let mut filter = Filter::All;
+        loop {
+            if filter.name() == name {
+                return Some(filter);
+            }
+            filter = filter.next();
+            if let Filter::All = filter {
+                return None;
+            }
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Filter::All => "all".to_string(),
+            Filter::Ungraded => "ungraded".to_string(),
+            Filter::Late => "late".to_string(),
+            Filter::FetchFailed => "fetch failed".to_string(),
+            Filter::Commented => "has comment".to_string(),
+            Filter::Below(grade) => format!("below {}", grade),
+        }
+    }
 }
 
 pub struct Model {
     pub config: Config,
     pub current: UiWidget,
     pub students: Vec<Student>,
+    // headers of extra columns in students csv
+    pub extra_columns: Vec<String>,
     pub status: Vec<String>,
 
     pub log: String,
@@ -56,6 +386,8 @@ pub struct Model {
 
     pub log_scroll_start: usize,
     pub log_lines: usize,
+    // lines scrolled back from the latest status
+    pub status_scroll_back: usize,
     pub diff_scroll_start: usize,
     pub diff_lines: usize,
 
@@ -71,6 +403,57 @@ pub struct Model {
 
     pub input_mode: InputMode,
     pub bottom_line: String,
+
+    // comment being edited
+    pub editor: Editor,
+    // set to hand the terminal to $EDITOR for the comment
+    pub edit_externally: bool,
+    // predefined comments, popup shown when one is selected
+    pub snippets: Vec<Snippet>,
+    pub snippet_select: Option<usize>,
+    // snippets picked for the comment being edited
+    pub picked: Vec<usize>,
+
+    // edits of grades and comments
+    pub undo: Vec<Edit>,
+    pub redo: Vec<Edit>,
+    // grades or comments changed since last save
+    pub dirty: bool,
+    // changes not in journal yet, and when journal was last written
+    pub journal_pending: bool,
+    pub journal_written: Instant,
+    // asking to save before quit
+    pub confirm_quit: bool,
+    // error of saving from the quit dialog
+    pub quit_error: Option<String>,
+
+    // whitebox criteria and the one being scored
+    pub rubric: Vec<Criterion>,
+    pub rubric_select: usize,
+    // parsed formula of total grade
+    pub total: Option<Expr>,
+
+    // branch of student repos to fetch
+    pub branch: String,
+
+    // help popup over panels
+    pub show_help: bool,
+    pub help_scroll: usize,
+    // audit log lines of selected student in popup
+    pub history: Option<Vec<String>>,
+    pub history_scroll: usize,
+
+    // results whose GitHub changed, waiting for confirmation:
+    // student, GitHub and grades of the row, written back as is until migrated
+    pub migrations: Vec<(usize, String, Grades)>,
+
+    // students shown in table and operated by bulk actions
+    pub filter: Filter,
+    pub search: String,
+    pub sort: SortKey,
+    pub sort_descending: bool,
+
+    pub areas: Areas,
 }
 
 impl Model {
@@ -79,6 +462,7 @@ impl Model {
         let config = self.config.clone();
         self.fetch_pool.execute(move || {
             let mut reset = false;
+            let mut success = false;
             if !Path::new(&config.workspace)
                 .join(&repo)
                 .join(".git")
@@ -151,7 +535,9 @@ impl Model {
                             .stderr(Stdio::null())
                             .status()
                             .unwrap();
-                        if !output.success() {
+                        if output.success() {
+                            success = true;
+                        } else {
                             tx.send(Message::Status(format!("Checkout {} failed", repo)))
                                 .unwrap();
                         }
@@ -164,26 +550,39 @@ impl Model {
                         .unwrap();
                 }
             }
+            let commit = head_commit(Path::new(&config.workspace).join(&repo));
+            tx.send(Message::Commit((repo.clone(), commit))).unwrap();
+            tx.send(Message::Fetch((repo, success))).unwrap();
         });
     }
 
-    fn git_grade(&self, index: usize, github: String) {
+    // read commit time of all repos in background
+    fn refresh_commits(&self) {
+        let all: Vec<usize> = (0..self.students.len()).collect();
+        for repo in self.repos_of(&all) {
+            let tx = self.tx_messages.clone();
+            let path = Path::new(&self.config.workspace).join(&repo);
+            self.fetch_pool.execute(move || {
+                tx.send(Message::Commit((repo, head_commit(path)))).unwrap();
+            });
+        }
+    }
+
+    fn git_grade(&self, indices: Vec<usize>, repo: String) {
         let tx = self.tx_messages.clone();
         let config = self.config.clone();
         self.grade_pool.execute(move || {
             if Path::new(&config.workspace)
-                .join(format!("{}-{}", config.prefix, github))
+                .join(&repo)
                 .join(".git")
                 .exists()
             {
                 for path in config.copy.iter() {
                     let orig_path = Path::new(&config.workspace)
                         .join(&config.template)
-                        .join(&path);
+                        .join(path);
                     if orig_path.is_dir() {
-                        let dest_path = Path::new(&config.workspace)
-                            .join(format!("{}-{}", config.prefix, github))
-                            .join(&path);
+                        let dest_path = Path::new(&config.workspace).join(&repo).join(path);
                         fs_extra::dir::remove(&dest_path).unwrap();
                         let mut options = fs_extra::dir::CopyOptions::new();
                         options.overwrite = true;
@@ -194,33 +593,32 @@ impl Model {
                         options.overwrite = true;
                         fs_extra::file::copy(
                             orig_path,
-                            Path::new(&config.workspace)
-                                .join(format!("{}-{}", config.prefix, github))
-                                .join(&path),
+                            Path::new(&config.workspace).join(&repo).join(path),
                             &options,
                         )
                         .unwrap();
                     }
                 }
 
-                let run_pwd = format!("{}/{}-{}", &config.workspace, &config.prefix, github);
+                let run_pwd = format!("{}/{}", &config.workspace, repo);
 
                 if let Some(ref before_grader) = config.before_grader {
                     tx.send(Message::Status(format!(
                         "Before grader procedure {} begin",
-                        github
+                        repo
                     )))
                     .unwrap();
 
                     execute::run(before_grader, &run_pwd);
                 }
 
-                tx.send(Message::Status(format!("Grading {} begin", github)))
+                tx.send(Message::Status(format!("Grading {} begin", repo)))
                     .unwrap();
 
+                let commit = head_commit(&run_pwd).map(|(hash, _)| hash);
                 let ans = execute::run(&config.grader, &run_pwd);
 
-                let grade = if let Ok(value) = serde_json::from_str::<Value>(&ans.trim()) {
+                let grade = if let Ok(value) = serde_json::from_str::<Value>(ans.trim()) {
                     if let Some(g) = value.get("grade") {
                         g.as_f64()
                     } else {
@@ -231,19 +629,248 @@ impl Model {
                 };
                 tx.send(Message::Status(format!(
                     "Grading {} ended with {:?}",
-                    github, grade
+                    repo, grade
                 )))
                 .unwrap();
-                tx.send(Message::Grade((index, grade))).unwrap();
+                for index in indices {
+                    tx.send(Message::Grade((index, grade, commit.clone())))
+                        .unwrap();
+                }
             } else {
-                tx.send(Message::Status(format!(
-                    "Grading {} repo not found",
-                    github
-                )))
-                .unwrap();
-                tx.send(Message::Grade((index, None))).unwrap();
+                tx.send(Message::Status(format!("Grading {} repo not found", repo)))
+                    .unwrap();
+                for index in indices {
+                    tx.send(Message::Grade((index, None, None))).unwrap();
+                }
+            }
+        });
+    }
+
+    // distinct repos of visible students, team repos only once
+    fn repos(&self) -> Vec<String> {
+        self.repos_of(&self.visible())
+    }
+
+    fn repos_of(&self, indices: &[usize]) -> Vec<String> {
+        let mut repos: Vec<String> = Vec::new();
+        for index in indices.iter().cloned() {
+            let repo = self.students[index].repo(&self.config.prefix);
+            if !repos.contains(&repo) {
+                repos.push(repo);
+            }
+        }
+        repos
+    }
+
+    // students sharing the repo
+    fn members(&self, repo: &str) -> Vec<usize> {
+        self.students
+            .iter()
+            .enumerate()
+            .filter(|(_, stu)| stu.repo(&self.config.prefix) == repo)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn matches(&self, stu: &Student) -> bool {
+        let filtered = match self.filter {
+            Filter::All => true,
+            Filter::Ungraded => stu.blackbox.is_none() || stu.whitebox.is_none(),
+            Filter::Late => stu.late(self.config.deadline),
+            Filter::FetchFailed => stu.fetch_failed,
+            Filter::Commented => stu.comment.as_ref().is_some_and(|c| !c.is_empty()),
+            Filter::Below(grade) => stu.blackbox.is_none_or(|g| g < grade),
+        };
+        if !filtered {
+            return false;
+        }
+        let search = self.search.to_lowercase();
+        search.is_empty()
+            || stu.student_id.to_lowercase().contains(&search)
+            || stu.name.to_lowercase().contains(&search)
+            || stu.github.to_lowercase().contains(&search)
+    }
+
+    // indices of students passing filter and search, in table order
+    pub fn visible(&self) -> Vec<usize> {
+        let mut visible: Vec<usize> = self
+            .students
+            .iter()
+            .enumerate()
+            .filter(|(_, stu)| self.matches(stu))
+            .map(|(index, _)| index)
+            .collect();
+        let deadline = self.config.deadline;
+        visible.sort_by(|x, y| {
+            let (a, b) = (&self.students[*x], &self.students[*y]);
+            let ordering = match self.sort {
+                SortKey::Roster => x.cmp(y),
+                SortKey::StudentId => a.student_id.cmp(&b.student_id),
+                SortKey::Name => a.name.cmp(&b.name),
+                SortKey::GitHub => a.github.to_lowercase().cmp(&b.github.to_lowercase()),
+                SortKey::Blackbox => a
+                    .blackbox
+                    .partial_cmp(&b.blackbox)
+                    .unwrap_or(Ordering::Equal),
+                SortKey::Whitebox => a
+                    .whitebox
+                    .partial_cmp(&b.whitebox)
+                    .unwrap_or(Ordering::Equal),
+                SortKey::Late => a.late(deadline).cmp(&b.late(deadline)),
+                SortKey::LastCommit => a.last_commit.cmp(&b.last_commit),
+            };
+            if self.sort_descending {
+                ordering.reverse()
+            } else {
+                ordering
             }
         });
+        visible
+    }
+
+    // keep selection inside visible students after filter or search changes
+    fn refilter(&mut self) {
+        let visible = self.visible();
+        if let Some(index) = self.student_select {
+            if visible.contains(&index) {
+                return;
+            }
+        }
+        self.student_select = visible.first().cloned();
+        self.student_render_start = 0;
+    }
+
+    // select next or previous visible student needing attention
+    fn jump_attention(&mut self, forward: bool) {
+        let mut visible = self.visible();
+        if !forward {
+            visible.reverse();
+        }
+        let pos = self
+            .student_select
+            .and_then(|index| visible.iter().position(|i| *i == index));
+        // search after current selection, wrapping around
+        let start = pos.map_or(0, |pos| pos + 1);
+        let found = visible
+            .iter()
+            .cycle()
+            .skip(start)
+            .take(visible.len())
+            .find(|index| self.students[**index].needs_attention());
+        if let Some(index) = found {
+            self.student_select = Some(*index);
+        } else {
+            self.status.push("No student needs attention\n".to_string());
+        }
+    }
+
+    // grades of students before an edit
+    fn snapshot(&self, indices: &[usize]) -> (Option<usize>, Vec<(usize, Grades)>) {
+        let grades = indices
+            .iter()
+            .map(|index| (*index, self.students[*index].grades()))
+            .collect();
+        (self.student_select, grades)
+    }
+
+    // record changes since snapshot as one edit
+    fn record_edit(&mut self, snapshot: (Option<usize>, Vec<(usize, Grades)>), source: Source) {
+        let (select_before, grades) = snapshot;
+        let changes: Vec<(usize, Grades, Grades)> = grades
+            .into_iter()
+            .map(|(index, before)| (index, before, self.students[index].grades()))
+            .filter(|(_, before, after)| before != after)
+            .collect();
+        if changes.is_empty() {
+            return;
+        }
+        for (index, before, after) in changes.iter() {
+            self.audit(*index, before, after, &source);
+        }
+        self.undo.push(Edit {
+            changes,
+            select_before,
+            select_after: self.student_select,
+        });
+        self.redo.clear();
+        self.mark_dirty();
+    }
+
+    // append changes of a student to audit log
+    fn audit(&mut self, index: usize, before: &Grades, after: &Grades, source: &Source) {
+        let changes = audit::changes(before, after, &self.rubric);
+        let path = audit::path(&self.config.results);
+        if let Err(err) = audit::log(&path, &self.students[index].student_id, &changes, source) {
+            self.status
+                .push(format!("Failed to write audit log {}: {}\n", path, err));
+        }
+    }
+
+    // undo last edit, or redo last undone one
+    fn undo_edit(&mut self, redo: bool) {
+        let edit = if redo {
+            self.redo.pop()
+        } else {
+            self.undo.pop()
+        };
+        let edit = match edit {
+            Some(edit) => edit,
+            None => {
+                self.status.push(format!(
+                    "Nothing to {}\n",
+                    if redo { "redo" } else { "undo" }
+                ));
+                return;
+            }
+        };
+        // only fields of the edit are restored, refused if changed since, e.g. by grader
+        let mut restored = Vec::new();
+        for (index, before, after) in edit.changes.iter() {
+            let current = self.students[*index].grades();
+            let (from, to) = if redo {
+                (before, after)
+            } else {
+                (after, before)
+            };
+            match current.apply(from, to) {
+                Ok(grades) => restored.push((*index, current, grades)),
+                Err(field) => {
+                    self.status.push(format!(
+                        "Cannot {}, {} of {} changed since\n",
+                        if redo { "redo" } else { "undo" },
+                        field,
+                        self.students[*index].name
+                    ));
+                    if redo {
+                        self.redo.push(edit);
+                    } else {
+                        self.undo.push(edit);
+                    }
+                    return;
+                }
+            }
+        }
+        for (index, current, grades) in restored.into_iter() {
+            let source = if redo { Source::Redo } else { Source::Undo };
+            self.audit(index, &current, &grades, &source);
+            self.students[index].set_grades(grades);
+        }
+        self.mark_dirty();
+        self.student_select = if redo {
+            edit.select_after
+        } else {
+            edit.select_before
+        };
+        self.status.push(format!(
+            "{} changes of {} students\n",
+            if redo { "Redid" } else { "Undid" },
+            edit.changes.len()
+        ));
+        if redo {
+            self.undo.push(edit);
+        } else {
+            self.redo.push(edit);
+        }
     }
 
     // true for whitebox, false for blackbox
@@ -261,130 +888,449 @@ impl Model {
                 } else {
                     None
                 };
-                let selector = if let Select::Whitebox = select {
-                    true
-                } else {
-                    false
-                };
+                let selector = matches!(select, Select::Whitebox);
                 (new_grade, selector)
             };
 
+            // next row as shown, before the grade may move this student in sorted table
+            let visible = self.visible();
+            let next = visible
+                .iter()
+                .position(|i| *i == index)
+                .and_then(|pos| visible.get(pos + 1))
+                .cloned();
+            let snapshot = self.snapshot(&[index]);
             if selector {
                 self.students[index].whitebox = new_grade;
             } else {
                 self.students[index].blackbox = new_grade;
             }
-            if index + 1 < self.students.len() {
-                self.student_select = Some(index + 1);
+            if let Some(next) = next {
+                self.student_select = Some(next);
             }
             self.last_grade = Some((new_grade, selector));
+            self.record_edit(
+                snapshot,
+                if let Select::Last = select {
+                    Source::Repeat
+                } else {
+                    Source::Manual
+                },
+            );
         }
     }
-    fn gen_results(&self) -> Vec<u8> {
+
+    // set score of selected criterion from grade buffer, whitebox is the sum of scores
+    // less what was deducted by snippets
+    fn score_criterion(&mut self) {
+        let index = match self.student_select {
+            Some(index) => index,
+            None => return,
+        };
+        let criterion = &self.rubric[self.rubric_select];
+        let score = self
+            .grade_buffer
+            .as_ref()
+            .and_then(|grade| grade.parse::<f64>().ok());
+        if let Some(score) = score {
+            if score < 0.0 || score > criterion.max {
+                self.status.push(format!(
+                    "Score {} is not between 0 and max {} of {}\n",
+                    score, criterion.max, criterion.name
+                ));
+                return;
+            }
+        }
+        let snapshot = self.snapshot(&[index]);
+        let whitebox_max = self.config.whitebox_max;
+        let stu = &mut self.students[index];
+        stu.rubric[self.rubric_select] = score;
+        stu.whitebox = match rubric::total(&stu.rubric) {
+            Some(total) => Some(total - stu.deduction),
+            // deducted from whitebox_max before any criterion was scored
+            None if stu.deduction != 0.0 => Some(whitebox_max - stu.deduction),
+            None => None,
+        };
+        self.record_edit(snapshot, Source::Rubric);
+        if self.rubric_select + 1 < self.rubric.len() {
+            self.rubric_select += 1;
+        }
+    }
+
+    fn gen_results(&self, format: &results::Format) -> Vec<u8> {
         let mut buffer = Vec::new();
-        // UTF-8 BOM
-        buffer.push(0xef);
-        buffer.push(0xbb);
-        buffer.push(0xbf);
-        let mut wtr = csv::Writer::from_writer(&mut buffer);
-        wtr.write_record(&["学号", "姓名", "GitHub", "黑盒成绩", "白盒成绩", "备注"])
-            .unwrap();
-        for stu in self.students.iter() {
-            let blackbox = if let Some(grade) = stu.blackbox {
-                grade.to_string()
-            } else {
-                format!("N/A")
-            };
-            let whitebox = if let Some(grade) = stu.whitebox {
-                grade.to_string()
-            } else {
-                format!("N/A")
-            };
-            let comment = if let Some(comment) = &stu.comment {
-                &comment
-            } else {
-                ""
+        if format.bom {
+            // UTF-8 BOM
+            buffer.extend_from_slice(&[0xef, 0xbb, 0xbf]);
+        }
+        let mut wtr = csv::WriterBuilder::new()
+            .delimiter(format.delimiter)
+            .from_writer(&mut buffer);
+        let criteria: Vec<String> = self.rubric.iter().map(|c| c.name.clone()).collect();
+        let (columns, _) = format.columns(&self.extra_columns, &criteria, self.total.is_some());
+        let header: Vec<String> = columns
+            .iter()
+            .map(|column| format.header(column, &criteria))
+            .collect();
+        wtr.write_record(&header).unwrap();
+        let ungraded = Grades {
+            rubric: vec![None; self.rubric.len()],
+            ..Grades::default()
+        };
+        for (index, stu) in self.students.iter().enumerate() {
+            // row of renamed GitHub is written back as is until migrated, unless graded since
+            let pending;
+            let stu = match self.migrations.iter().find(|(i, _, _)| *i == index) {
+                Some((_, github, grades)) if stu.grades() == ungraded => {
+                    let mut renamed = stu.clone();
+                    renamed.github = github.clone();
+                    renamed.set_grades(grades.clone());
+                    pending = renamed;
+                    &pending
+                }
+                _ => stu,
             };
-
-            wtr.write_record(&[
-                &stu.student_id,
-                &stu.name,
-                &stu.github,
-                &blackbox,
-                &whitebox,
-                comment,
-            ])
-            .unwrap();
+            let record: Vec<String> = columns
+                .iter()
+                .map(|column| match column {
+                    Column::Field(0) => stu.student_id.clone(),
+                    Column::Field(1) => stu.name.clone(),
+                    Column::Field(2) => stu.github.clone(),
+                    Column::Field(3) => format.grade(stu.blackbox),
+                    Column::Field(4) => format.grade(stu.whitebox),
+                    Column::Field(_) => stu.comment.clone().unwrap_or_default(),
+                    Column::Extra(name) => stu.extra.get(name).cloned().unwrap_or_default(),
+                    Column::Criterion(index) => {
+                        format.grade(stu.rubric.get(*index).cloned().flatten())
+                    }
+                    Column::Deduction => stu.deduction.to_string(),
+                    Column::Total => format.grade(self.total(stu)),
+                })
+                .collect();
+            wtr.write_record(&record).unwrap();
         }
         wtr.flush().unwrap();
         drop(wtr);
         buffer
     }
+    // total grade by formula, None without formula or if a grade used is missing
+    pub fn total(&self, stu: &Student) -> Option<f64> {
+        let total = self.total.as_ref()?;
+        let days_late = match (stu.last_commit, self.config.deadline) {
+            (Some(last_commit), Some(deadline)) if last_commit > deadline => {
+                (last_commit - deadline).num_seconds() as f64 / 86400.0
+            }
+            _ => 0.0,
+        };
+        let mut variables = HashMap::new();
+        variables.insert("blackbox".to_string(), stu.blackbox);
+        variables.insert("whitebox".to_string(), stu.whitebox);
+        let late = if stu.late(self.config.deadline) {
+            1.0
+        } else {
+            0.0
+        };
+        variables.insert("late".to_string(), Some(late));
+        variables.insert("days_late".to_string(), Some(days_late));
+        variables.insert(
+            "late_penalty".to_string(),
+            Some(self.config.late_penalty * days_late.ceil()),
+        );
+        for (criterion, score) in self.rubric.iter().zip(stu.rubric.iter()) {
+            variables.insert(formula::variable(&criterion.name), *score);
+        }
+        total.eval(&variables)
+    }
+
+    fn gen_json(&self) -> Vec<u8> {
+        let grade = |grade: Option<f64>| grade.map_or(Value::Null, Value::from);
+        let mut students = Vec::new();
+        for stu in self.students.iter() {
+            let mut object = serde_json::Map::new();
+            object.insert("student_id".to_string(), stu.student_id.clone().into());
+            object.insert("name".to_string(), stu.name.clone().into());
+            object.insert("github".to_string(), stu.github.clone().into());
+            object.insert("blackbox".to_string(), grade(stu.blackbox));
+            object.insert("whitebox".to_string(), grade(stu.whitebox));
+            object.insert(
+                "comment".to_string(),
+                stu.comment.clone().unwrap_or_default().into(),
+            );
+            for column in self.extra_columns.iter() {
+                object.insert(
+                    column.clone(),
+                    stu.extra.get(column).cloned().unwrap_or_default().into(),
+                );
+            }
+            for (criterion, score) in self.rubric.iter().zip(stu.rubric.iter()) {
+                object.insert(criterion.name.clone(), grade(*score));
+            }
+            if !self.rubric.is_empty() {
+                object.insert("deduction".to_string(), stu.deduction.into());
+            }
+            if self.total.is_some() {
+                object.insert("total".to_string(), grade(self.total(stu)));
+            }
+            students.push(Value::Object(object));
+        }
+        serde_json::to_vec_pretty(&students).unwrap()
+    }
+
+    fn mark_dirty(&mut self) {
+        self.dirty = true;
+        self.journal_pending = true;
+    }
+
+    // error is also pushed to status
+    fn save(&mut self) -> Result<(), String> {
+        let buffer = self.gen_results(&self.config.result_format);
+
+        if let Err(err) = results::write_atomic(&self.config.results, &buffer) {
+            let error = format!("Failed to save {}: {}", self.config.results, err);
+            self.status.push(format!("{}\n", error));
+            return Err(error);
+        }
+        self.status
+            .push(format!("Saved to {}\n", self.config.results));
+        if !self.migrations.is_empty() {
+            self.status.push(format!(
+                "Kept results of {} renamed GitHub accounts as they were, press M to migrate\n",
+                self.migrations.len()
+            ));
+        }
+        self.dirty = false;
+        self.journal_pending = false;
+        std::fs::remove_file(results::journal_path(&self.config.results)).ok();
+        Ok(())
+    }
+
+    // write unsaved edits to journal, recovered on next start after a crash
+    fn autosave(&mut self) {
+        if !self.journal_pending
+            || self.config.autosave == 0
+            || self.journal_written.elapsed() < Duration::from_secs(self.config.autosave)
+        {
+            return;
+        }
+        let journal = results::journal_path(&self.config.results);
+        // journal keeps all columns to be read back whatever the result format is
+        let buffer = self.gen_results(&results::Format::default());
+        if let Err(err) = results::write_atomic(&journal, &buffer) {
+            self.status
+                .push(format!("Failed to autosave {}: {}\n", journal, err));
+        }
+        self.journal_pending = false;
+        self.journal_written = Instant::now();
+    }
+
+    // returns true to quit now, otherwise asks to save first
+    fn quit(&mut self) -> bool {
+        if self.dirty {
+            self.confirm_quit = true;
+            false
+        } else {
+            true
+        }
+    }
+
+    fn handle_quit(&mut self, key: KeyCode) -> bool {
+        match key {
+            // stay in the dialog if results could not be written
+            KeyCode::Char('s') | KeyCode::Char('y') => match self.save() {
+                Ok(()) => true,
+                Err(error) => {
+                    self.quit_error = Some(error);
+                    false
+                }
+            },
+            KeyCode::Char('d') | KeyCode::Char('n') => {
+                std::fs::remove_file(results::journal_path(&self.config.results)).ok();
+                true
+            }
+            KeyCode::Esc | KeyCode::Char('c') => {
+                self.confirm_quit = false;
+                self.quit_error = None;
+                false
+            }
+            _ => false,
+        }
+    }
+
     pub fn new(config: Config) -> Model {
         let mut status = Vec::new();
 
         // read students
-        let mut rdr = csv::Reader::from_reader(File::open(&config.students).unwrap());
-        let mut students = Vec::new();
-        for row in rdr.records() {
-            // cols: student_id, name, github
-            let record = row.unwrap();
-            let student_id = record.get(0).unwrap();
-            let name = record.get(1).unwrap();
-            let github = record.get(2).unwrap();
-            students.push(Student {
-                student_id: String::from(student_id),
-                name: String::from(name),
-                github: String::from(github),
-                blackbox: None,
-                whitebox: None,
-                comment: None,
+        let (extra_columns, mut students) = roster::read(&config, &mut status);
+        if let Some(teams) = &config.teams {
+            roster::read_teams(teams, &mut students, &mut status);
+        }
+        if let Some(ta) = &config.ta {
+            let assigned = tas::assign(&config.tas, &students, &mut status);
+            let mut assigned = assigned.iter();
+            students.retain(|_| assigned.next().unwrap().as_ref() == Some(ta));
+            status.push(format!(
+                "Grading {} students of TA {}, saving to {}\n",
+                students.len(),
+                ta,
+                config.results
+            ));
+        }
+        let snippets = match &config.snippets {
+            Some(path) => snippets::read(path, &mut status),
+            None => Vec::new(),
+        };
+        let criteria = match &config.rubric {
+            Some(path) => rubric::read(path, &mut status),
+            None => Vec::new(),
+        };
+        for stu in students.iter_mut() {
+            stu.rubric = vec![None; criteria.len()];
+        }
+        let names: Vec<String> = criteria.iter().map(|c| c.name.clone()).collect();
+        // variables of total formula, besides criteria
+        let mut variables: Vec<String> = formula::RESERVED
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        variables.extend(names.iter().map(|name| formula::variable(name)));
+        let total = config
+            .total
+            .as_ref()
+            .and_then(|text| match formula::parse(text, &variables) {
+                Ok(expr) => Some(expr),
+                Err(err) => {
+                    status.push(format!("{}, total not computed\n", err));
+                    None
+                }
             });
+        let (columns, unknown) =
+            config
+                .result_format
+                .columns(&extra_columns, &names, total.is_some());
+        for name in unknown.iter() {
+            status.push(format!("Result column {} not found, skipped\n", name));
+        }
+        if !columns
+            .iter()
+            .any(|column| matches!(column, Column::Field(0)))
+        {
+            status.push("Results without student_id column cannot be loaded back\n".to_string());
         }
 
-        // read existed results
-        if Path::new(&config.results).exists() {
-            let mut rdr = csv::Reader::from_reader(File::open(&config.results).unwrap());
-            for row in rdr.records() {
-                // cols: student_id, name, github, blackbox, whitebox, comment
-                let record = row.unwrap();
-                let student_id = record.get(0).unwrap();
-                let name = record.get(1).unwrap();
-                let github = record.get(2).unwrap();
-                for stu in students.iter_mut() {
-                    if stu.student_id == student_id && stu.name == name && stu.github == github {
-                        if let Some(blackbox) = record.get(3) {
-                            if let Ok(grade) = blackbox.parse() {
-                                stu.blackbox = Some(grade);
-                            } else {
-                                stu.blackbox = None;
-                            }
-                        }
-                        if let Some(whitebox) = record.get(4) {
-                            if let Ok(grade) = whitebox.parse() {
-                                stu.whitebox = Some(grade);
-                            } else {
-                                stu.whitebox = None;
-                            }
-                        }
-                        if let Some(comment) = record.get(5) {
-                            stu.comment = Some(String::from(comment));
-                        }
-                        break;
+        // unsaved edits of last session are loaded instead of results
+        let journal = results::journal_path(&config.results);
+        let dirty = results::journal_newer(&journal, &config.results);
+        let load = if dirty {
+            status.push(format!(
+                "Recovered unsaved edits from {}, press s to save\n",
+                journal
+            ));
+            journal
+        } else {
+            if Path::new(&journal).exists() {
+                status.push(format!("Removed {} older than results\n", journal));
+                std::fs::remove_file(&journal).ok();
+            }
+            config.results.clone()
+        };
+
+        // read existed results, matched by student id
+        let mut migrations: Vec<(usize, String, Grades)> = Vec::new();
+        if Path::new(&load).exists() {
+            let mut loaded = vec![false; students.len()];
+            // journal is written with all columns whatever the result format is
+            let format = if dirty {
+                results::Format::default()
+            } else {
+                config.result_format.clone()
+            };
+            for record in results::read(&load, &format, &mut status) {
+                let index = match students
+                    .iter()
+                    .position(|stu| stu.student_id == record.student_id)
+                {
+                    Some(index) => index,
+                    None => {
+                        status.push(format!(
+                            "Result of {} {} not found in students\n",
+                            record.student_id, record.name
+                        ));
+                        continue;
                     }
+                };
+                let stu = &mut students[index];
+                if loaded[index] {
+                    if stu.blackbox != record.blackbox
+                        || stu.whitebox != record.whitebox
+                        || stu.comment != record.comment
+                    {
+                        status.push(format!(
+                            "Conflicting results of {} {}, using the first one\n",
+                            stu.student_id, stu.name
+                        ));
+                    }
+                    continue;
+                }
+                // name and GitHub may be left out of result columns
+                if !record.name.is_empty() && stu.name != record.name {
+                    status.push(format!(
+                        "Name of {} changed from {} to {}\n",
+                        stu.student_id, record.name, stu.name
+                    ));
+                }
+                let grades = Grades {
+                    blackbox: record.blackbox,
+                    whitebox: record.whitebox,
+                    comment: record.comment.or_else(|| stu.comment.clone()),
+                    rubric: rubric::scores(&criteria, &record.fields),
+                    deduction: record
+                        .fields
+                        .get("deduction")
+                        .and_then(|deduction| deduction.parse().ok())
+                        .unwrap_or(0.0),
+                };
+                if !record.github.is_empty() && stu.github != record.github {
+                    if !migrations.iter().any(|(i, _, _)| *i == index) {
+                        migrations.push((index, record.github, grades));
+                    }
+                    continue;
+                }
+                loaded[index] = true;
+                stu.set_grades(grades);
+            }
+            // a row with matching GitHub wins over the renamed one
+            migrations.retain(|(index, _, _)| !loaded[*index]);
+            for (index, github, _) in migrations.iter() {
+                status.push(format!(
+                    "GitHub of {} changed from {} to {}, press M to migrate grades\n",
+                    students[*index].student_id, github, students[*index].github
+                ));
+            }
+
+            // values not from edits in the log were changed outside, e.g. by merge
+            let path = audit::path(&config.results);
+            let last = audit::last_changes(&path);
+            for stu in students.iter() {
+                let changes = audit::imported(&stu.student_id, &stu.grades(), &criteria, &last);
+                let source = Source::Import(load.clone());
+                if let Err(err) = audit::log(&path, &stu.student_id, &changes, &source) {
+                    status.push(format!("Failed to write audit log {}: {}\n", path, err));
+                    break;
                 }
             }
         }
 
+        status.extend(config.keys.errors.iter().cloned());
         status.push(format!("Read {} students from data\n", students.len()));
         status.push(format!("Deadline is {:?}\n", config.deadline));
 
         let (tx, rx) = mpsc::channel();
 
-        Model {
+        let mut model = Model {
             config,
             current: UiWidget::Student,
             students,
+            extra_columns,
             status,
             student_select: None,
             student_render_start: 0,
@@ -394,6 +1340,7 @@ impl Model {
 
             log_scroll_start: 0,
             log_lines: 0,
+            status_scroll_back: 0,
             diff_scroll_start: 0,
             diff_lines: 0,
 
@@ -408,87 +1355,637 @@ impl Model {
 
             input_mode: InputMode::Normal,
             bottom_line: String::new(),
+            editor: Editor::default(),
+            edit_externally: false,
+            snippets,
+            snippet_select: None,
+            picked: Vec::new(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            dirty,
+            journal_pending: false,
+            journal_written: Instant::now(),
+            confirm_quit: false,
+            quit_error: None,
+            rubric: criteria,
+            rubric_select: 0,
+            total,
+            branch: "master".to_string(),
+            show_help: false,
+            help_scroll: 0,
+            history: None,
+            history_scroll: 0,
+
+            migrations,
+
+            filter: Filter::All,
+            search: String::new(),
+            sort: SortKey::Roster,
+            sort_descending: false,
+
+            areas: Areas::default(),
+        };
+        model.restore_session();
+        model.refresh_commits();
+        model
+    }
+
+    pub fn save_session(&mut self) {
+        let session = Session {
+            select: self
+                .student_select
+                .map(|index| self.students[index].student_id.clone()),
+            current: self.current.name().to_string(),
+            student_render_start: self.student_render_start,
+            log_scroll_start: self.log_scroll_start,
+            diff_scroll_start: self.diff_scroll_start,
+            status_scroll_back: self.status_scroll_back,
+            filter: self.filter.name(),
+            search: self.search.clone(),
+            sort: self.sort.name().to_string(),
+            sort_descending: self.sort_descending,
+            last_grade: self.last_grade,
+            rubric_select: self.rubric_select,
+            branch: self.branch.clone(),
+            graded_commits: self
+                .students
+                .iter()
+                .filter_map(|stu| Some((stu.student_id.clone(), stu.graded_commit.clone()?)))
+                .collect(),
+        };
+        let path = session::path(&self.config.workspace, &self.config.prefix, &self.config.ta);
+        if let Err(err) = session.save(&path) {
+            self.status
+                .push(format!("Failed to save {}: {}\n", path.display(), err));
         }
     }
 
-    pub fn handle(&mut self, key: KeyCode) -> bool {
-        if let InputMode::Text = self.input_mode {
-            let index = self.student_select.unwrap();
-            match key {
-                KeyCode::Esc => {
-                    self.input_mode = InputMode::Normal;
-                    self.status.push(format!(
-                        "Editing comment for user {} done\n",
-                        self.students[index].name,
-                    ));
-                    self.students[index].comment = Some(self.bottom_line.clone());
-                    self.bottom_line.clear();
+    // restore ui state of last launch, students may have changed since
+    fn restore_session(&mut self) {
+        let path = session::path(&self.config.workspace, &self.config.prefix, &self.config.ta);
+        let session = match Session::load(&path) {
+            Some(session) => session,
+            None => return,
+        };
+        self.current = UiWidget::parse(&session.current).unwrap_or(UiWidget::Student);
+        if let UiWidget::Rubric = self.current {
+            if self.rubric.is_empty() {
+                self.current = UiWidget::Student;
+            }
+        }
+        self.filter = Filter::parse(&session.filter).unwrap_or(Filter::All);
+        self.search = session.search;
+        self.sort = SortKey::parse(&session.sort).unwrap_or(SortKey::Roster);
+        self.sort_descending = session.sort_descending;
+        self.last_grade = session.last_grade;
+        self.rubric_select =
+            std::cmp::min(session.rubric_select, self.rubric.len().saturating_sub(1));
+        if !session.branch.is_empty() {
+            self.branch = session.branch;
+        }
+        // to tell repos changed since graded in an earlier launch
+        for stu in self.students.iter_mut() {
+            if stu.graded_commit.is_none() {
+                stu.graded_commit = session.graded_commits.get(&stu.student_id).cloned();
+            }
+        }
+
+        self.student_select = session.select.and_then(|student_id| {
+            self.students
+                .iter()
+                .position(|stu| stu.student_id == student_id)
+        });
+        // not refiltered, late students are only known after commits are read
+        self.student_render_start = session.student_render_start;
+        if self.student_select.is_some() {
+            self.on_select();
+        }
+        // scroll positions after on_select resets them
+        self.log_scroll_start =
+            std::cmp::min(session.log_scroll_start, self.log_lines.saturating_sub(1));
+        self.diff_scroll_start =
+            std::cmp::min(session.diff_scroll_start, self.diff_lines.saturating_sub(1));
+        self.status_scroll_back = session.status_scroll_back;
+        self.status
+            .push(format!("Restored session from {}\n", path.display()));
+    }
+
+    // selection changed, load log and diff of selected student
+    fn on_select(&mut self) {
+        let student = match self.student_select {
+            Some(index) => &self.students[index],
+            None => {
+                self.bottom_line.clear();
+                return;
+            }
+        };
+        if let Some(team) = &student.team {
+            self.status
+                .push(format!("Looking at {} of team {}\n", student.name, team));
+        } else {
+            self.status.push(format!("Looking at {}\n", student.name));
+        }
+        self.bottom_line = student.comment.clone().unwrap_or_default();
+
+        let repo = student.repo(&self.config.prefix);
+        if Path::new(&self.config.workspace)
+            .join(&repo)
+            .join(".git")
+            .exists()
+        {
+            let output = Command::new("git")
+                .current_dir(format!("{}/{}", self.config.workspace, repo))
+                .arg("log")
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .output()
+                .unwrap();
+            self.log = String::from_utf8(output.stdout)
+                .unwrap()
+                .replace("\t", "    ");
+            self.log_lines = self.log.chars().filter(|ch| *ch == '\n').count();
+            self.log_scroll_start = 0;
+
+            // clear first
+            self.diff = String::from("Waiting...");
+            self.diff_lines = 1;
+            self.diff_scroll_start = 0;
+
+            let output = Command::new("git")
+                .current_dir(format!("{}/{}", self.config.workspace, repo))
+                .arg("log")
+                .arg("-p")
+                .arg(&self.config.diff)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .output()
+                .unwrap();
+            self.diff = String::from_utf8_lossy(&output.stdout).replace("\t", "    ");
+            self.diff_lines = self.diff.chars().filter(|ch| *ch == '\n').count();
+            self.diff_scroll_start = 0;
+        } else {
+            self.log = String::from("N/A");
+            self.log_lines = 1;
+            self.log_scroll_start = 0;
+
+            self.diff = String::from("N/A");
+            self.diff_lines = 1;
+            self.diff_scroll_start = 0;
+        }
+    }
+
+    fn handle_search(&mut self, key: KeyCode) {
+        let orig_student_select = self.student_select;
+        match key {
+            KeyCode::Esc | KeyCode::Enter => {
+                self.input_mode = InputMode::Normal;
+                if let KeyCode::Esc = key {
+                    self.search.clear();
+                }
+                self.bottom_line = self
+                    .student_select
+                    .and_then(|index| self.students[index].comment.clone())
+                    .unwrap_or_default();
+            }
+            KeyCode::Backspace => {
+                self.bottom_line.pop();
+                self.search = self.bottom_line.clone();
+            }
+            KeyCode::Char(ch) => {
+                self.bottom_line.push(ch);
+                self.search = self.bottom_line.clone();
+            }
+            _ => {
+                self.status.push(format!("Unhandled key {:?}\n", key));
+            }
+        }
+        self.refilter();
+        if orig_student_select != self.student_select {
+            self.on_select();
+        }
+    }
+
+    // students chosen by selectors of a command, among visible ones unless "all"
+    fn select_students(&self, selectors: &[Selector]) -> Vec<usize> {
+        let mut indices: Vec<usize> = if selectors.iter().any(|s| matches!(s, Selector::All)) {
+            (0..self.students.len()).collect()
+        } else {
+            self.visible()
+        };
+        let deadline = self.config.deadline;
+        indices.retain(|index| {
+            let stu = &self.students[*index];
+            selectors.iter().all(|selector| match selector {
+                Selector::All => true,
+                Selector::Selected => self.student_select == Some(*index),
+                Selector::Ungraded => stu.blackbox.is_none() || stu.whitebox.is_none(),
+                Selector::Late => stu.late(deadline),
+                Selector::Failed => stu.fetch_failed || stu.grader_error,
+                Selector::Attention => stu.needs_attention(),
+                Selector::Commented => stu.comment.as_ref().is_some_and(|c| !c.is_empty()),
+                Selector::Column(column, value) if column == "team" => {
+                    stu.team.as_ref() == Some(value)
+                }
+                Selector::Column(column, value) => stu.extra.get(column) == Some(value),
+            })
+        });
+        indices
+    }
+
+    // returns true to quit
+    fn run_command(&mut self, line: &str) -> bool {
+        let command = match command::parse(line) {
+            Ok(command) => command,
+            Err(err) => {
+                self.status.push(format!("{}\n", err));
+                return false;
+            }
+        };
+        if let command::Command::Grade(selectors)
+        | command::Command::Fetch(selectors)
+        | command::Command::Set(_, _, selectors) = &command
+        {
+            for selector in selectors.iter() {
+                if let Selector::Column(column, _) = selector {
+                    if column != "team" && !self.extra_columns.contains(column) {
+                        self.status
+                            .push(format!("Column {} not found in students csv\n", column));
+                        return false;
+                    }
+                }
+            }
+        }
+
+        match command {
+            command::Command::Grade(selectors) => {
+                let repos = self.repos_of(&self.select_students(&selectors));
+                self.status.push(format!("Grading {} repos\n", repos.len()));
+                for repo in repos {
+                    self.git_grade(self.members(&repo), repo);
+                }
+            }
+            command::Command::Fetch(selectors) => {
+                let repos = self.repos_of(&self.select_students(&selectors));
+                self.status
+                    .push(format!("Fetching {} repos\n", repos.len()));
+                for repo in repos {
+                    self.git_fetch(repo, self.branch.clone());
+                }
+            }
+            command::Command::Set(whitebox, grade, selectors) => {
+                let indices = self.select_students(&selectors);
+                let snapshot = self.snapshot(&indices);
+                for index in indices.iter() {
+                    if whitebox {
+                        self.students[*index].whitebox = grade;
+                    } else {
+                        self.students[*index].blackbox = grade;
+                    }
+                }
+                self.status.push(format!(
+                    "Set {} of {} students to {}\n",
+                    if whitebox { "whitebox" } else { "blackbox" },
+                    indices.len(),
+                    grade.map_or("N/A".to_string(), |grade| grade.to_string())
+                ));
+                self.record_edit(snapshot, Source::Command);
+            }
+            command::Command::Export(format, path) => {
+                let buffer = match format {
+                    Format::Csv => self.gen_results(&self.config.result_format),
+                    Format::Json => self.gen_json(),
+                };
+                match results::write_atomic(&path, &buffer) {
+                    Ok(()) => self.status.push(format!("Exported to {}\n", path)),
+                    Err(err) => self
+                        .status
+                        .push(format!("Failed to export to {}: {}\n", path, err)),
+                }
+            }
+            command::Command::Branch(branch) => {
+                self.status
+                    .push(format!("Fetching branch {} of student repos\n", branch));
+                self.branch = branch;
+            }
+            command::Command::Save => {
+                self.save().ok();
+            }
+            command::Command::Help => {
+                self.status.push("Commands: \n".to_string());
+                for usage in command::USAGE.iter() {
+                    self.status.push(format!("       {}\n", usage));
+                }
+                self.status.push(
+                    "Selectors: all selected ungraded late failed attention commented team=<team> <column>=<value>\n"
+                        .to_string(),
+                );
+            }
+            command::Command::Quit => {
+                return self.quit();
+            }
+        }
+        false
+    }
+
+    // returns true to quit
+    fn handle_command(&mut self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::Enter | KeyCode::Esc => {
+                let line = std::mem::take(&mut self.bottom_line);
+                self.input_mode = InputMode::Normal;
+                self.bottom_line = self
+                    .student_select
+                    .and_then(|index| self.students[index].comment.clone())
+                    .unwrap_or_default();
+                if let KeyCode::Enter = key {
+                    return self.run_command(&line);
+                }
+            }
+            KeyCode::Tab => {
+                let (line, candidates) = command::complete(&self.bottom_line, &self.extra_columns);
+                self.bottom_line = line;
+                if !candidates.is_empty() {
+                    self.status.push(format!("{}\n", candidates.join(" ")));
+                }
+            }
+            KeyCode::Backspace => {
+                self.bottom_line.pop();
+                // deleting ":" leaves command line
+                if self.bottom_line.is_empty() {
+                    return self.handle_command(KeyCode::Esc);
                 }
-                KeyCode::Backspace => {
-                    self.bottom_line.pop();
+            }
+            KeyCode::Char(ch) => {
+                self.bottom_line.push(ch);
+            }
+            _ => {
+                self.status.push(format!("Unhandled key {:?}\n", key));
+            }
+        }
+        false
+    }
+
+    fn handle_snippet(&mut self, key: KeyCode) {
+        let select = self.snippet_select.unwrap();
+        match key {
+            KeyCode::Esc => self.snippet_select = None,
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.snippet_select = Some((select + 1) % self.snippets.len());
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.snippet_select =
+                    Some((select + self.snippets.len() - 1) % self.snippets.len());
+            }
+            KeyCode::Enter => {
+                let snippet = &self.snippets[select];
+                self.editor.append_line(&snippet.text);
+                self.picked.push(select);
+                self.snippet_select = None;
+            }
+            // pick one of the first nine directly
+            KeyCode::Char(ch @ '1'..='9') => {
+                let pos = ch as usize - '1' as usize;
+                if pos < self.snippets.len() {
+                    self.snippet_select = Some(pos);
+                    self.handle_snippet(KeyCode::Enter);
                 }
-                KeyCode::Char(ch) => {
-                    self.bottom_line.push(ch);
+            }
+            _ => {
+                self.status.push(format!("Unhandled key {:?}\n", key));
+            }
+        }
+    }
+
+    fn handle_comment(&mut self, event: KeyEvent) {
+        if self.snippet_select.is_some() {
+            self.handle_snippet(event.code);
+            return;
+        }
+        let index = self.student_select.unwrap();
+        let ctrl = event.modifiers.contains(KeyModifiers::CONTROL);
+        match event.code {
+            KeyCode::Tab => {
+                if self.snippets.is_empty() {
+                    self.status
+                        .push("No snippets, set snippets in config\n".to_string());
+                } else {
+                    self.snippet_select = Some(0);
                 }
-                _ => {
-                    self.status.push(format!("Unhandled key {:?}\n", key));
+            }
+            KeyCode::Esc => {
+                self.input_mode = InputMode::Normal;
+                self.status.push(format!(
+                    "Editing comment for user {} cancelled\n",
+                    self.students[index].name,
+                ));
+            }
+            KeyCode::Enter if event.modifiers.is_empty() => self.commit_comment(index),
+            KeyCode::Char('s') if ctrl => self.commit_comment(index),
+            KeyCode::Char('e') if ctrl => self.edit_externally = true,
+            _ => {
+                if !self.editor.handle(&event) {
+                    self.status
+                        .push(format!("Unhandled key {:?}\n", event.code));
                 }
             }
+        }
+    }
+
+    // whitebox points deducted by picked snippets still in the comment being edited
+    pub fn snippet_deduction(&self) -> f64 {
+        let before = self
+            .student_select
+            .and_then(|index| self.students[index].comment.clone())
+            .unwrap_or_default();
+        let count = |text: &str, line: &str| text.lines().filter(|l| *l == line).count();
+        let mut picked = self.picked.clone();
+        picked.sort_unstable();
+        picked.dedup();
+        picked
+            .iter()
+            .map(|index| {
+                let snippet = &self.snippets[*index];
+                // lines added while editing, up to the times picked
+                let added = count(&self.editor.text, &snippet.text)
+                    .saturating_sub(count(&before, &snippet.text));
+                let times = self.picked.iter().filter(|i| *i == index).count();
+                std::cmp::min(added, times) as f64 * snippet.deduction.unwrap_or(0.0)
+            })
+            .sum()
+    }
+
+    fn commit_comment(&mut self, index: usize) {
+        let deduction = self.snippet_deduction();
+        let snapshot = self.snapshot(&[index]);
+        self.input_mode = InputMode::Normal;
+        self.status.push(format!(
+            "Editing comment for user {} done\n",
+            self.students[index].name,
+        ));
+        self.students[index].comment = Some(self.editor.text.clone());
+        self.bottom_line = self.editor.text.clone();
+        if deduction != 0.0 {
+            let stu = &mut self.students[index];
+            let whitebox = stu.whitebox.unwrap_or(self.config.whitebox_max) - deduction;
+            stu.whitebox = Some(whitebox);
+            // kept when whitebox is summed from rubric scores again
+            stu.deduction += deduction;
+            self.status.push(format!(
+                "Deducted {} from whitebox of {}, now {}\n",
+                deduction, stu.name, whitebox
+            ));
+        }
+        let source = if deduction != 0.0 {
+            Source::Snippet
+        } else {
+            Source::Manual
+        };
+        self.record_edit(snapshot, source);
+    }
+
+    // edit comment in $VISUAL or $EDITOR, terminal must be released by caller
+    pub fn run_editor(&mut self) {
+        self.edit_externally = false;
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+        let path =
+            std::env::temp_dir().join(format!("classroom-comment-{}.txt", std::process::id()));
+        if let Err(err) = std::fs::write(&path, &self.editor.text) {
+            self.status
+                .push(format!("Failed to write {}: {}\n", path.display(), err));
+            return;
+        }
+        // editor may come with arguments, e.g. "code --wait"
+        let mut words = editor.split_whitespace();
+        let result = Command::new(words.next().unwrap_or("vi"))
+            .args(words)
+            .arg(&path)
+            .status();
+        match result {
+            Ok(status) if status.success() => match std::fs::read_to_string(&path) {
+                Ok(text) => {
+                    // editors add a final newline
+                    self.editor = Editor::new(text.trim_end_matches('\n').to_string());
+                }
+                Err(err) => {
+                    self.status
+                        .push(format!("Failed to read {}: {}\n", path.display(), err))
+                }
+            },
+            Ok(status) => self
+                .status
+                .push(format!("Editor {} exited with {}\n", editor, status)),
+            Err(err) => self
+                .status
+                .push(format!("Failed to run editor {}: {}\n", editor, err)),
+        }
+        std::fs::remove_file(&path).ok();
+    }
+
+    pub fn handle(&mut self, event: KeyEvent) -> bool {
+        let key = event.code;
+        if self.confirm_quit {
+            return self.handle_quit(key);
+        }
+        if self.history.is_some() {
+            match self.config.keys.action(&event) {
+                Some(Action::History) => self.history = None,
+                _ if key == KeyCode::Esc => self.history = None,
+                Some(Action::Down) => self.history_scroll += 1,
+                Some(Action::Up) => self.history_scroll = self.history_scroll.saturating_sub(1),
+                _ => {}
+            }
+            return false;
+        }
+        if self.show_help {
+            match self.config.keys.action(&event) {
+                Some(Action::Help) => self.show_help = false,
+                _ if key == KeyCode::Esc => self.show_help = false,
+                Some(Action::Down) => self.help_scroll += 1,
+                Some(Action::Up) => self.help_scroll = self.help_scroll.saturating_sub(1),
+                _ => {}
+            }
+            return false;
+        }
+        if let InputMode::Text(TextInput::Search) = self.input_mode {
+            self.handle_search(key);
             return false;
         }
+        if let InputMode::Text(TextInput::Command) = self.input_mode {
+            return self.handle_command(key);
+        }
+        if let InputMode::Text(TextInput::Comment) = self.input_mode {
+            self.handle_comment(event);
+            return false;
+        }
+
+        if let KeyCode::Char(ch) = key {
+            if event.modifiers.is_empty() && (ch.is_ascii_digit() || ch == '.') {
+                if let Some(buffer) = &mut self.grade_buffer {
+                    buffer.push(ch);
+                } else {
+                    self.grade_buffer = Some(ch.to_string());
+                }
+                return false;
+            }
+        }
 
         let orig_student_select = self.student_select;
-        let mut update_grade = false;
-        match key {
+        match self.config.keys.action(&event) {
             // change current widget
-            KeyCode::Char('H') => {
+            Some(Action::FocusLeft) => {
                 self.current = match self.current {
                     UiWidget::Student => UiWidget::Student,
                     UiWidget::Status => UiWidget::Status,
                     UiWidget::Log => UiWidget::Student,
                     UiWidget::Diff => UiWidget::Student,
+                    UiWidget::Rubric => UiWidget::Student,
                 };
             }
-            KeyCode::Char('J') => {
+            Some(Action::FocusDown) => {
                 self.current = match self.current {
                     UiWidget::Student => UiWidget::Status,
                     UiWidget::Status => UiWidget::Status,
+                    UiWidget::Log if !self.rubric.is_empty() => UiWidget::Rubric,
                     UiWidget::Log => UiWidget::Diff,
                     UiWidget::Diff => UiWidget::Diff,
+                    UiWidget::Rubric => UiWidget::Diff,
                 };
             }
-            KeyCode::Char('K') => {
+            Some(Action::FocusUp) => {
                 self.current = match self.current {
                     UiWidget::Student => UiWidget::Student,
                     UiWidget::Status => UiWidget::Student,
                     UiWidget::Log => UiWidget::Log,
+                    UiWidget::Diff if !self.rubric.is_empty() => UiWidget::Rubric,
                     UiWidget::Diff => UiWidget::Log,
+                    UiWidget::Rubric => UiWidget::Log,
                 };
             }
-            KeyCode::Char('L') => {
+            Some(Action::FocusRight) => {
                 self.current = match self.current {
                     UiWidget::Student => UiWidget::Log,
                     UiWidget::Status => UiWidget::Diff,
                     UiWidget::Log => UiWidget::Log,
                     UiWidget::Diff => UiWidget::Diff,
+                    UiWidget::Rubric => UiWidget::Rubric,
                 };
             }
-            KeyCode::Char('j') => {
+            Some(Action::Down) => {
                 match self.current {
                     UiWidget::Student => {
-                        self.student_select = match self.student_select {
-                            None => {
-                                if self.students.len() > 0 {
-                                    Some(0)
-                                } else {
-                                    None
-                                }
-                            }
+                        let visible = self.visible();
+                        let pos = self
+                            .student_select
+                            .and_then(|index| visible.iter().position(|i| *i == index));
+                        self.student_select = match pos {
+                            None => visible.first().cloned(),
                             Some(current) => {
-                                if self.students.len() > current + 1 {
-                                    Some(current + 1)
+                                if visible.len() > current + 1 {
+                                    Some(visible[current + 1])
                                 } else {
-                                    Some(0)
+                                    Some(visible[0])
                                 }
                             }
                         }
@@ -507,25 +2004,30 @@ impl Model {
                             0
                         };
                     }
-                    _ => {}
+                    UiWidget::Status => {
+                        self.status_scroll_back = self.status_scroll_back.saturating_sub(1);
+                    }
+                    UiWidget::Rubric => {
+                        if self.rubric_select + 1 < self.rubric.len() {
+                            self.rubric_select += 1;
+                        }
+                    }
                 };
             }
-            KeyCode::Char('k') => {
+            Some(Action::Up) => {
                 match self.current {
                     UiWidget::Student => {
-                        self.student_select = match self.student_select {
-                            None => {
-                                if self.students.len() > 0 {
-                                    Some(self.students.len() - 1)
-                                } else {
-                                    None
-                                }
-                            }
+                        let visible = self.visible();
+                        let pos = self
+                            .student_select
+                            .and_then(|index| visible.iter().position(|i| *i == index));
+                        self.student_select = match pos {
+                            None => visible.last().cloned(),
                             Some(current) => {
                                 if current > 0 {
-                                    Some(current - 1)
+                                    Some(visible[current - 1])
                                 } else {
-                                    Some(self.students.len() - 1)
+                                    Some(visible[visible.len() - 1])
                                 }
                             }
                         }
@@ -534,41 +2036,30 @@ impl Model {
                         self.log_scroll_start = if self.log_scroll_start > 0 {
                             self.log_scroll_start - 1
                         } else {
-                            self.log_lines - 1
+                            self.log_lines.saturating_sub(1)
                         };
                     }
                     UiWidget::Diff => {
                         self.diff_scroll_start = if self.diff_scroll_start > 0 {
                             self.diff_scroll_start - 1
                         } else {
-                            self.diff_lines - 1
+                            self.diff_lines.saturating_sub(1)
                         };
                     }
-                    _ => {}
+                    UiWidget::Status => {
+                        // clamped to the first line when drawing
+                        self.status_scroll_back += 1;
+                    }
+                    UiWidget::Rubric => {
+                        self.rubric_select = self.rubric_select.saturating_sub(1);
+                    }
                 };
             }
-            KeyCode::Char('h') | KeyCode::Char('?') => {
-                self.status.push(format!("Usage: \n"));
-                self.status
-                    .push(format!("       H J K L: navigate between panels\n"));
-                self.status.push(format!("       j k: scroll in panels\n"));
-                self.status
-                    .push(format!("       f F: fetch selected(f)/all(F) students\n"));
-                self.status.push(format!(
-                    "       g G: grade blackbox for selected(g)/all(G) students\n"
-                ));
-                self.status
-                    .push(format!("       s d: save(s)/diff(d) results\n"));
-                self.status.push(format!(
-                    "       [num]+b w: set blackbox(b)/whitebox(w) grade manually\n"
-                ));
-                self.status
-                    .push(format!("       r: repeat last grade for current student\n"));
-                self.status
-                    .push(format!("       t: bump template repo to newest version\n"));
-                self.status.push(format!("       c: edit comment\n"));
+            Some(Action::Help) => {
+                self.show_help = true;
+                self.help_scroll = 0;
             }
-            KeyCode::Char('d') => {
+            Some(Action::Diff) => {
                 let results = if Path::new(&self.config.results).is_file() {
                     &self.config.results
                 } else {
@@ -586,171 +2077,265 @@ impl Model {
                     .spawn()
                     .unwrap();
 
-                let buffer = self.gen_results();
+                let buffer = self.gen_results(&self.config.result_format);
 
-                spawn.stdin.as_mut().unwrap().write(&buffer).unwrap();
+                spawn.stdin.as_mut().unwrap().write_all(&buffer).unwrap();
                 let out = spawn.wait_with_output().unwrap();
                 self.diff = String::from_utf8(out.stdout).unwrap().replace("\t", "    ");
-                if self.diff.len() > 0 {
+                if !self.diff.is_empty() {
                     self.diff_lines = self.diff.chars().filter(|ch| *ch == '\n').count();
                     self.diff_scroll_start = 0;
                 } else {
-                    self.diff = format!("No difference");
+                    self.diff = "No difference".to_string();
                     self.diff_lines = 1;
                     self.diff_scroll_start = 0;
                 }
             }
-            KeyCode::Char('s') => {
-                let buffer = self.gen_results();
-
-                let mut file = File::create(&self.config.results).unwrap();
-                file.write_all(&buffer).unwrap();
-                self.status
-                    .push(format!("Saved to {}\n", self.config.results));
-            }
-            KeyCode::Char(ch) if (ch >= '0' && ch <= '9') || ch == '.' => {
-                if let Some(buffer) = &mut self.grade_buffer {
-                    buffer.push(ch);
-                } else {
-                    self.grade_buffer = Some(ch.to_string());
-                }
-                update_grade = true;
+            Some(Action::Save) => {
+                self.save().ok();
             }
-            KeyCode::Char('b') => {
+            Some(Action::Blackbox) => {
                 self.update_grade(Select::Blackbox);
             }
-            KeyCode::Char('w') => {
-                self.update_grade(Select::Whitebox);
+            Some(Action::Whitebox) => {
+                if let UiWidget::Rubric = self.current {
+                    self.score_criterion();
+                } else {
+                    self.update_grade(Select::Whitebox);
+                }
             }
-            KeyCode::Char('r') => {
+            Some(Action::Repeat) => {
                 self.update_grade(Select::Last);
             }
-            KeyCode::Char('f') => {
+            Some(Action::Fetch) => {
                 if let Some(index) = self.student_select {
                     self.git_fetch(
-                        format!(
-                            "{}-{}",
-                            self.config.prefix,
-                            self.students[index].github.clone(),
-                        ),
-                        format!("master"),
+                        self.students[index].repo(&self.config.prefix),
+                        self.branch.clone(),
                     );
                 }
             }
-            KeyCode::Char('F') => {
+            Some(Action::FetchAll) => {
                 self.git_fetch(
                     self.config.template.clone(),
                     self.config.template_branch.clone(),
                 );
-                for stu in self.students.iter() {
-                    self.git_fetch(
-                        format!("{}-{}", self.config.prefix, stu.github.clone()),
-                        format!("master"),
-                    );
+                for repo in self.repos() {
+                    self.git_fetch(repo, self.branch.clone());
                 }
             }
-            KeyCode::Char('g') => {
+            Some(Action::Grade) => {
                 if let Some(index) = self.student_select {
-                    self.git_grade(index, self.students[index].github.clone());
+                    let repo = self.students[index].repo(&self.config.prefix);
+                    self.git_grade(self.members(&repo), repo);
                 }
             }
-            KeyCode::Char('G') => {
-                for (index, stu) in self.students.iter().enumerate() {
-                    self.git_grade(index, stu.github.clone());
+            Some(Action::GradeAll) => {
+                for repo in self.repos() {
+                    self.git_grade(self.members(&repo), repo);
                 }
             }
-            KeyCode::Char('t') => {
+            Some(Action::Template) => {
                 self.git_fetch(
                     self.config.template.clone(),
                     self.config.template_branch.clone(),
                 );
             }
-            KeyCode::Char('c') => {
+            Some(Action::Command) => {
+                self.input_mode = InputMode::Text(TextInput::Command);
+                self.bottom_line = ":".to_string();
+            }
+            Some(Action::Search) => {
+                self.input_mode = InputMode::Text(TextInput::Search);
+                self.bottom_line = self.search.clone();
+            }
+            Some(Action::Filter) => {
+                self.filter = if let Some(grade) = self
+                    .grade_buffer
+                    .as_ref()
+                    .and_then(|grade| grade.parse::<f64>().ok())
+                {
+                    Filter::Below(grade)
+                } else {
+                    self.filter.next()
+                };
+                self.status
+                    .push(format!("Showing {} students\n", self.filter.name()));
+                self.refilter();
+            }
+            Some(Action::NextAttention) => {
+                self.jump_attention(true);
+            }
+            Some(Action::PrevAttention) => {
+                self.jump_attention(false);
+            }
+            Some(Action::Sort) => {
+                self.sort = self.sort.next();
+                self.status
+                    .push(format!("Sorting students by {}\n", self.sort.name()));
+            }
+            Some(Action::SortReverse) => {
+                self.sort_descending = !self.sort_descending;
+                self.status.push(format!(
+                    "Sorting students by {} in {} order\n",
+                    self.sort.name(),
+                    if self.sort_descending {
+                        "descending"
+                    } else {
+                        "ascending"
+                    }
+                ));
+            }
+            Some(Action::ClearFilter) => {
+                self.filter = Filter::All;
+                self.search.clear();
+                self.refilter();
+            }
+            Some(Action::Comment) => {
                 if let Some(index) = self.student_select {
-                    self.input_mode = InputMode::Text;
+                    self.input_mode = InputMode::Text(TextInput::Comment);
                     self.status.push(format!(
                         "Editing comment for user {}\n",
                         self.students[index].name,
                     ));
-                    self.bottom_line = self.students[index].comment.clone().unwrap_or_default();
+                    self.editor =
+                        Editor::new(self.students[index].comment.clone().unwrap_or_default());
+                    self.picked.clear();
                 }
             }
-            KeyCode::Char('q') => {
-                // qui
-                return true;
+            Some(Action::Migrate) => {
+                if self.migrations.is_empty() {
+                    self.status.push("No grades to migrate\n".to_string());
+                }
+                let indices: Vec<usize> =
+                    self.migrations.iter().map(|(index, _, _)| *index).collect();
+                let snapshot = self.snapshot(&indices);
+                for (index, github, grades) in self.migrations.drain(..) {
+                    let stu = &mut self.students[index];
+                    stu.set_grades(grades);
+                    self.status.push(format!(
+                        "Migrated grades of {} from {} to {}\n",
+                        stu.student_id, github, stu.github
+                    ));
+                }
+                self.record_edit(snapshot, Source::Import(self.config.results.clone()));
             }
-            _ => {
+            Some(Action::Undo) => {
+                self.undo_edit(false);
+            }
+            Some(Action::Redo) => {
+                self.undo_edit(true);
+            }
+            Some(Action::History) => {
+                if let Some(index) = self.student_select {
+                    let path = audit::path(&self.config.results);
+                    let history = audit::history(&path, &self.students[index].student_id);
+                    let mut lines: Vec<String> = history
+                        .iter()
+                        .map(|row| {
+                            // time, user, field, old, new, source
+                            format!(
+                                "{} {} {}: {} -> {} ({})",
+                                row[0],
+                                row[1],
+                                row[2],
+                                row[3].replace('\n', " ↵ "),
+                                row[4].replace('\n', " ↵ "),
+                                row[5]
+                            )
+                        })
+                        .collect();
+                    if lines.is_empty() {
+                        lines.push("No changes recorded".to_string());
+                    }
+                    // newest first screen
+                    self.history_scroll = lines.len();
+                    self.history = Some(lines);
+                }
+            }
+            Some(Action::Quit) => {
+                return self.quit();
+            }
+            None => {
                 self.status.push(format!("Unhandled key {:?}\n", key));
             }
         }
 
-        if !update_grade {
-            self.grade_buffer = None;
-        }
+        self.grade_buffer = None;
 
         if orig_student_select != self.student_select {
-            // Selection changed
-            let student = &self.students[self.student_select.unwrap()];
-            self.status.push(format!("Looking at {}\n", student.name));
-            self.bottom_line = student.comment.clone().unwrap_or_default();
-
-            if Path::new(&self.config.workspace)
-                .join(format!("{}-{}", self.config.prefix, student.github))
-                .join(".git")
-                .exists()
-            {
-                let output = Command::new("git")
-                    .current_dir(format!(
-                        "{}/{}-{}",
-                        self.config.workspace, self.config.prefix, student.github
-                    ))
-                    .arg("log")
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::null())
-                    .output()
-                    .unwrap();
-                self.log = String::from_utf8(output.stdout)
-                    .unwrap()
-                    .replace("\t", "    ");
-                self.log_lines = self.log.chars().filter(|ch| *ch == '\n').count();
-                self.log_scroll_start = 0;
-
-                // clear first
-                self.diff = String::from("Waiting...");
-                self.diff_lines = 1;
-                self.diff_scroll_start = 0;
-
-                let output = Command::new("git")
-                    .current_dir(format!(
-                        "{}/{}-{}",
-                        self.config.workspace, self.config.prefix, student.github
-                    ))
-                    .arg("log")
-                    .arg("-p")
-                    .arg(&self.config.diff)
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::null())
-                    .output()
-                    .unwrap();
-                self.diff = String::from_utf8_lossy(&output.stdout)
-                    .to_owned()
-                    .replace("\t", "    ");
-                self.diff_lines = self.diff.chars().filter(|ch| *ch == '\n').count();
-                self.diff_scroll_start = 0;
-            } else {
-                self.log = String::from("N/A");
-                self.log_lines = 1;
-                self.log_scroll_start = 0;
+            self.on_select();
+        }
+        false
+    }
 
-                self.diff = String::from("N/A");
-                self.diff_lines = 1;
-                self.diff_scroll_start = 0;
+    pub fn handle_mouse(&mut self, event: MouseEvent) {
+        if let InputMode::Text(_) = self.input_mode {
+            return;
+        }
+        let widget = match self.areas.widget(event.column, event.row) {
+            Some(widget) => widget,
+            None => return,
+        };
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.current = widget;
+                if let UiWidget::Student = widget {
+                    // skip top border and header
+                    let row = event.row.saturating_sub(self.areas.student.y) as usize;
+                    if row >= 2 {
+                        let orig_student_select = self.student_select;
+                        if let Some(index) = self.visible().get(self.student_render_start + row - 2)
+                        {
+                            self.student_select = Some(*index);
+                        }
+                        if orig_student_select != self.student_select {
+                            self.on_select();
+                        }
+                    }
+                }
+                if let UiWidget::Rubric = widget {
+                    // skip top border
+                    let row = event.row.saturating_sub(self.areas.rubric.y) as usize;
+                    if row >= 1 && row <= self.rubric.len() {
+                        self.rubric_select = row - 1;
+                    }
+                }
             }
+            MouseEventKind::ScrollDown => match widget {
+                UiWidget::Log => {
+                    self.log_scroll_start =
+                        std::cmp::min(self.log_scroll_start + 1, self.log_lines.saturating_sub(1));
+                }
+                UiWidget::Diff => {
+                    self.diff_scroll_start = std::cmp::min(
+                        self.diff_scroll_start + 1,
+                        self.diff_lines.saturating_sub(1),
+                    );
+                }
+                UiWidget::Status => {
+                    self.status_scroll_back = self.status_scroll_back.saturating_sub(1);
+                }
+                _ => {}
+            },
+            MouseEventKind::ScrollUp => match widget {
+                UiWidget::Log => {
+                    self.log_scroll_start = self.log_scroll_start.saturating_sub(1);
+                }
+                UiWidget::Diff => {
+                    self.diff_scroll_start = self.diff_scroll_start.saturating_sub(1);
+                }
+                UiWidget::Status => {
+                    self.status_scroll_back += 1;
+                }
+                _ => {}
+            },
+            _ => {}
         }
-        return false;
     }
 
     pub fn tick(&mut self) {
+        self.autosave();
         while let Ok(message) = self.rx_messages.try_recv() {
             match message {
                 Message::Status(status) => {
@@ -760,8 +2345,32 @@ impl Model {
                         self.fetch_pool.queued_count() + self.grade_pool.queued_count()
                     ));
                 }
-                Message::Grade((index, grade)) => {
-                    self.students[index].blackbox = grade;
+                Message::Grade((index, grade, commit)) => {
+                    let before = self.students[index].grades();
+                    let stu = &mut self.students[index];
+                    stu.blackbox = grade;
+                    stu.grader_error = grade.is_none();
+                    stu.graded_commit = commit.clone();
+                    let after = stu.grades();
+                    if before != after {
+                        self.audit(index, &before, &after, &Source::Grader(commit));
+                        self.mark_dirty();
+                    }
+                }
+                Message::Fetch((repo, success)) => {
+                    for stu in self.students.iter_mut() {
+                        if stu.repo(&self.config.prefix) == repo {
+                            stu.fetch_failed = !success;
+                        }
+                    }
+                }
+                Message::Commit((repo, commit)) => {
+                    for stu in self.students.iter_mut() {
+                        if stu.repo(&self.config.prefix) == repo {
+                            stu.head = commit.as_ref().map(|(hash, _)| hash.clone());
+                            stu.last_commit = commit.as_ref().map(|(_, time)| *time);
+                        }
+                    }
                 }
             }
         }
@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+// One row of a results csv written by Model::gen_results
+pub struct Record {
+    pub student_id: String,
+    pub name: String,
+    pub github: String,
+    pub blackbox: Option<f64>,
+    pub whitebox: Option<f64>,
+    pub comment: Option<String>,
+    // columns after comment by header, e.g. rubric scores
+    pub fields: HashMap<String, String>,
+}
+
+// columns always known to results, in default order
+pub const FIELDS: [&str; 6] = [
+    "student_id",
+    "name",
+    "github",
+    "blackbox",
+    "whitebox",
+    "comment",
+];
+
+const LABELS_ZH: [&str; 6] = ["学号", "姓名", "GitHub", "黑盒成绩", "白盒成绩", "备注"];
+const LABELS_EN: [&str; 6] = [
+    "Student ID",
+    "Name",
+    "GitHub",
+    "Blackbox",
+    "Whitebox",
+    "Comment",
+];
+
+// layout of results csv
+#[derive(Clone)]
+pub struct Format {
+    // fields, extra column or criterion names, "extra" and "rubric" for all of them,
+    // "total" for total grade if a formula is configured
+    pub columns: Vec<String>,
+    // "zh" or "en" labels of fields
+    pub language: String,
+    // header of a column overriding language, by lowercase column name
+    pub headers: HashMap<String, String>,
+    pub bom: bool,
+    pub delimiter: u8,
+    // written for missing grades and scores
+    pub missing: String,
+    // decimal places of grades, shortest if None
+    pub precision: Option<usize>,
+}
+
+impl Default for Format {
+    fn default() -> Format {
+        Format {
+            columns: FIELDS
+                .iter()
+                .chain(["extra", "rubric", "total"].iter())
+                .map(|column| column.to_string())
+                .collect(),
+            language: "zh".to_string(),
+            headers: HashMap::new(),
+            bom: true,
+            delimiter: b',',
+            missing: "N/A".to_string(),
+            precision: None,
+        }
+    }
+}
+
+// a column of results csv, resolved against roster and rubric
+pub enum Column {
+    // index into FIELDS
+    Field(usize),
+    Extra(String),
+    // index into rubric criteria
+    Criterion(usize),
+    // snippet points deducted from rubric scores
+    Deduction,
+    Total,
+}
+
+impl Format {
+    // columns to write and names matching nothing
+    pub fn columns(
+        &self,
+        extra: &[String],
+        criteria: &[String],
+        total: bool,
+    ) -> (Vec<Column>, Vec<String>) {
+        let mut columns = Vec::new();
+        let mut unknown = Vec::new();
+        for name in self.columns.iter() {
+            if let Some(index) = FIELDS.iter().position(|field| field == name) {
+                columns.push(Column::Field(index));
+            } else if name == "extra" {
+                columns.extend(extra.iter().cloned().map(Column::Extra));
+            } else if name == "rubric" {
+                columns.extend((0..criteria.len()).map(Column::Criterion));
+                if !criteria.is_empty() {
+                    columns.push(Column::Deduction);
+                }
+            } else if name == "total" {
+                if total {
+                    columns.push(Column::Total);
+                }
+            } else if extra.contains(name) {
+                columns.push(Column::Extra(name.clone()));
+            } else if let Some(index) = criteria.iter().position(|criterion| criterion == name) {
+                columns.push(Column::Criterion(index));
+            } else {
+                unknown.push(name.clone());
+            }
+        }
+        (columns, unknown)
+    }
+
+    pub fn header(&self, column: &Column, criteria: &[String]) -> String {
+        let name = match column {
+            Column::Field(index) => FIELDS[*index],
+            Column::Extra(name) => name,
+            Column::Criterion(index) => &criteria[*index],
+            Column::Deduction => "deduction",
+            Column::Total => "total",
+        };
+        if let Some(header) = self.headers.get(&name.to_lowercase()) {
+            return header.clone();
+        }
+        match column {
+            Column::Field(index) if self.language == "en" => LABELS_EN[*index].to_string(),
+            Column::Field(index) => LABELS_ZH[*index].to_string(),
+            Column::Total if self.language == "en" => "Total".to_string(),
+            Column::Total => "总评".to_string(),
+            _ => name.to_string(),
+        }
+    }
+
+    pub fn grade(&self, grade: Option<f64>) -> String {
+        match (grade, self.precision) {
+            (None, _) => self.missing.clone(),
+            (Some(grade), Some(precision)) => format!("{:.*}", precision, grade),
+            (Some(grade), None) => grade.to_string(),
+        }
+    }
+
+    // column name of a header relabeled in config
+    fn name(&self, header: &str) -> String {
+        self.headers
+            .iter()
+            .find(|(_, label)| *label == header)
+            .map_or(header, |(name, _)| name)
+            .to_string()
+    }
+
+    // position of each field in headers, or by position if student_id is not found
+    pub fn positions(&self, headers: &[String]) -> Vec<Option<usize>> {
+        if self.find(headers, 0).is_some() {
+            (0..FIELDS.len())
+                .map(|index| self.find(headers, index))
+                .collect()
+        } else {
+            (0..FIELDS.len()).map(Some).collect()
+        }
+    }
+
+    // position of field in headers by any label it may have been written with
+    fn find(&self, headers: &[String], index: usize) -> Option<usize> {
+        let field = FIELDS[index];
+        let mut labels = vec![field, LABELS_ZH[index], LABELS_EN[index]];
+        if let Some(header) = self.headers.get(field) {
+            labels.push(header);
+        }
+        headers.iter().position(|header| {
+            labels
+                .iter()
+                .any(|label| label.eq_ignore_ascii_case(header))
+        })
+    }
+}
+
+// delimiter of a csv by its header line, preferring the configured one
+pub fn sniff_delimiter(text: &str, preferred: u8) -> u8 {
+    let line = text.lines().next().unwrap_or("");
+    [preferred, b',', b';', b'\t', b'|']
+        .iter()
+        .cloned()
+        .find(|delimiter| line.contains(*delimiter as char))
+        .unwrap_or(preferred)
+}
+
+// NaN and inf parse as f64 but are no grades
+fn parse_grade(grade: &str) -> Option<f64> {
+    grade.parse().ok().filter(|grade: &f64| grade.is_finite())
+}
+
+// read results by header, or by position for files without known headers
+pub fn read(path: &str, format: &Format, status: &mut Vec<String>) -> Vec<Record> {
+    let mut records = Vec::new();
+    let text = fs::read_to_string(path).unwrap();
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(sniff_delimiter(&text, format.delimiter))
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let headers: Vec<String> = rdr
+        .headers()
+        .unwrap()
+        .iter()
+        .map(|header| header.trim_start_matches('\u{feff}').to_string())
+        .collect();
+    // cols: student_id, name, github, blackbox, whitebox, comment
+    let positions = format.positions(&headers);
+    // grades would silently read as missing
+    for index in 3..5 {
+        if positions[index].is_none() && format.columns.iter().any(|c| c == FIELDS[index]) {
+            status.push(format!(
+                "Column {} not found in {}, set its header in config\n",
+                FIELDS[index], path
+            ));
+        }
+    }
+    for row in rdr.records() {
+        let record = row.unwrap();
+        let get = |index: usize| positions[index].and_then(|position| record.get(position));
+        records.push(Record {
+            student_id: get(0).unwrap_or("").to_string(),
+            name: get(1).unwrap_or("").to_string(),
+            github: get(2).unwrap_or("").to_string(),
+            blackbox: get(3).and_then(parse_grade),
+            whitebox: get(4).and_then(parse_grade),
+            comment: get(5).map(String::from),
+            fields: headers
+                .iter()
+                .zip(record.iter())
+                .enumerate()
+                .filter(|(position, _)| !positions.contains(&Some(*position)))
+                .map(|(_, (header, value))| (format.name(header), value.to_string()))
+                .collect(),
+        });
+    }
+    records
+}
+
+// write to a temporary file and rename it, so a crash never leaves a truncated file
+pub fn write_atomic<P: AsRef<Path>>(path: P, buffer: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(buffer)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+// autosaved results not saved yet, next to the results file
+pub fn journal_path(results: &str) -> String {
+    format!("{}.journal", results)
+}
+
+// whether journal has edits newer than results
+pub fn journal_newer(journal: &str, results: &str) -> bool {
+    let modified = |path: &str| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    match (modified(journal), modified(results)) {
+        (Some(journal), Some(results)) => journal >= results,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    // read text saved to a temporary file named name
+    fn read_text(name: &str, text: &str, format: &Format) -> (Vec<Record>, Vec<String>) {
+        let path = std::env::temp_dir().join(format!("results-test-{}.csv", name));
+        fs::write(&path, text).unwrap();
+        let mut status = Vec::new();
+        let records = read(path.to_str().unwrap(), format, &mut status);
+        fs::remove_file(&path).unwrap();
+        (records, status)
+    }
+
+    #[test]
+    fn positions() {
+        let format = Format::default();
+        let headers = strings(&["GitHub", "学号", "Blackbox", "comment"]);
+        assert_eq!(
+            format.positions(&headers),
+            vec![Some(1), None, Some(0), Some(2), None, Some(3)]
+        );
+        // by position without a student id header
+        let headers = strings(&["a", "b", "c"]);
+        assert_eq!(
+            format.positions(&headers),
+            (0..FIELDS.len()).map(Some).collect::<Vec<_>>()
+        );
+        // relabeled in config
+        let mut format = Format::default();
+        format
+            .headers
+            .insert("student_id".to_string(), "ID".to_string());
+        format
+            .headers
+            .insert("blackbox".to_string(), "Score".to_string());
+        let headers = strings(&["id", "Score"]);
+        assert_eq!(
+            format.positions(&headers),
+            vec![Some(0), None, None, Some(1), None, None]
+        );
+    }
+
+    #[test]
+    fn sniff() {
+        assert_eq!(sniff_delimiter("a;b;c\n1,2;3", b','), b';');
+        assert_eq!(sniff_delimiter("a\tb", b','), b'\t');
+        assert_eq!(sniff_delimiter("a,b;c", b';'), b';');
+        assert_eq!(sniff_delimiter("a", b','), b',');
+        assert_eq!(sniff_delimiter("", b'|'), b'|');
+    }
+
+    #[test]
+    fn read_default() {
+        let text = "\u{feff}学号,姓名,GitHub,黑盒成绩,白盒成绩,备注,Style\n\
+                    1,A,a,80,N/A,good,5\n\
+                    2,B,b,NaN,90.5,,\n";
+        let (records, status) = read_text("default", text, &Format::default());
+        assert!(status.is_empty());
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].student_id, "1");
+        assert_eq!(records[0].blackbox, Some(80.0));
+        assert_eq!(records[0].whitebox, None);
+        assert_eq!(records[0].comment.as_deref(), Some("good"));
+        assert_eq!(
+            records[0].fields.get("Style").map(String::as_str),
+            Some("5")
+        );
+        assert_eq!(records[1].blackbox, None);
+        assert_eq!(records[1].whitebox, Some(90.5));
+    }
+
+    #[test]
+    fn read_relabeled() {
+        let mut format = Format::default();
+        format
+            .headers
+            .insert("student_id".to_string(), "ID".to_string());
+        format
+            .headers
+            .insert("blackbox".to_string(), "Score".to_string());
+        format
+            .headers
+            .insert("style".to_string(), "Code style".to_string());
+        let text = "Score;ID;Code style;Feedback\n70;1;8;ok\n";
+        let (records, status) = read_text("relabeled", text, &format);
+        assert_eq!(
+            status,
+            vec![format!(
+                "Column whitebox not found in {}, set its header in config\n",
+                std::env::temp_dir()
+                    .join("results-test-relabeled.csv")
+                    .to_str()
+                    .unwrap()
+            )]
+        );
+        assert_eq!(records[0].student_id, "1");
+        assert_eq!(records[0].blackbox, Some(70.0));
+        assert_eq!(records[0].whitebox, None);
+        assert_eq!(records[0].comment, None);
+        // criteria are keyed by name, other columns by header
+        assert_eq!(
+            records[0].fields.get("style").map(String::as_str),
+            Some("8")
+        );
+        assert_eq!(
+            records[0].fields.get("Feedback").map(String::as_str),
+            Some("ok")
+        );
+    }
+
+    #[test]
+    fn header_and_grade() {
+        let mut format = Format::default();
+        assert_eq!(format.header(&Column::Field(0), &[]), "学号");
+        assert_eq!(format.header(&Column::Total, &[]), "总评");
+        format.language = "en".to_string();
+        format
+            .headers
+            .insert("comment".to_string(), "Feedback".to_string());
+        assert_eq!(format.header(&Column::Field(0), &[]), "Student ID");
+        assert_eq!(format.header(&Column::Field(5), &[]), "Feedback");
+        assert_eq!(
+            format.header(&Column::Criterion(0), &strings(&["Style"])),
+            "Style"
+        );
+        assert_eq!(format.grade(Some(80.0)), "80");
+        assert_eq!(format.grade(None), "N/A");
+        format.precision = Some(1);
+        assert_eq!(format.grade(Some(80.25)), "80.2");
+    }
+
+    #[test]
+    fn columns() {
+        let format = Format::default();
+        let (columns, unknown) =
+            format.columns(&strings(&["section"]), &strings(&["Style"]), false);
+        assert_eq!(columns.len(), FIELDS.len() + 3);
+        assert!(matches!(columns[6], Column::Extra(ref name) if name == "section"));
+        assert!(matches!(columns[7], Column::Criterion(0)));
+        assert!(matches!(columns[8], Column::Deduction));
+        assert!(unknown.is_empty());
+        let format = Format {
+            columns: strings(&["student_id", "section", "Style", "total", "rank"]),
+            ..Format::default()
+        };
+        let (columns, unknown) = format.columns(&strings(&["section"]), &strings(&["Style"]), true);
+        assert_eq!(columns.len(), 4);
+        assert!(matches!(columns[3], Column::Total));
+        assert_eq!(unknown, strings(&["rank"]));
+    }
+}
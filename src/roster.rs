@@ -0,0 +1,196 @@
+use crate::configs::Config;
+use crate::model::Student;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+
+// locate a column by configured header name, or by position if not configured
+pub fn find_column(
+    headers: &[String],
+    name: &Option<String>,
+    default: usize,
+    status: &mut Vec<String>,
+) -> Option<usize> {
+    if let Some(name) = name {
+        let index = headers.iter().position(|header| header == name);
+        if index.is_none() {
+            status.push(format!("Column {} not found in students csv\n", name));
+        }
+        index
+    } else if default < headers.len() {
+        Some(default)
+    } else {
+        status.push(format!("Students csv has only {} columns\n", headers.len()));
+        None
+    }
+}
+
+// read students csv, returns headers of extra columns and students
+pub fn read(config: &Config, status: &mut Vec<String>) -> (Vec<String>, Vec<Student>) {
+    let mut extra_columns = Vec::new();
+    let mut students = Vec::new();
+
+    let file = match File::open(&config.students) {
+        Ok(file) => file,
+        Err(err) => {
+            status.push(format!("Failed to open {}: {}\n", config.students, err));
+            return (extra_columns, students);
+        }
+    };
+    let mut rdr = csv::ReaderBuilder::new().flexible(true).from_reader(file);
+    let headers: Vec<String> = match rdr.headers() {
+        Ok(headers) => headers
+            .iter()
+            .map(|header| header.trim_start_matches('\u{feff}').trim().to_string())
+            .collect(),
+        Err(err) => {
+            status.push(format!("Failed to read {}: {}\n", config.students, err));
+            return (extra_columns, students);
+        }
+    };
+
+    let columns = &config.columns;
+    let student_id = find_column(&headers, &columns.student_id, 0, status);
+    let name = find_column(&headers, &columns.name, 1, status);
+    let github = find_column(&headers, &columns.github, 2, status);
+    let (student_id, name, github) = match (student_id, name, github) {
+        (Some(student_id), Some(name), Some(github)) => (student_id, name, github),
+        _ => return (extra_columns, students),
+    };
+
+    let mut extra = Vec::new();
+    if let Some(names) = &columns.extra {
+        for name in names.iter() {
+            if let Some(index) = headers.iter().position(|header| header == name) {
+                extra.push((name.clone(), index));
+            } else {
+                status.push(format!("Column {} not found in students csv\n", name));
+            }
+        }
+    } else {
+        for (index, header) in headers.iter().enumerate() {
+            if index != student_id && index != name && index != github && !header.is_empty() {
+                extra.push((header.clone(), index));
+            }
+        }
+    }
+    extra_columns = extra.iter().map(|(name, _)| name.clone()).collect();
+
+    let mut seen_ids = HashSet::new();
+    let mut seen_githubs = HashSet::new();
+    for row in rdr.records() {
+        let record = match row {
+            Ok(record) => record,
+            Err(err) => {
+                status.push(format!("Skipping malformed row: {}\n", err));
+                continue;
+            }
+        };
+        let line = record.position().map(|pos| pos.line()).unwrap_or(0);
+        let get = |index: usize| record.get(index).unwrap_or("").trim();
+
+        if get(student_id).is_empty() {
+            status.push(format!("Line {}: missing student id, skipped\n", line));
+            continue;
+        }
+        if get(github).is_empty() {
+            status.push(format!(
+                "Line {}: missing GitHub of {}, skipped\n",
+                line,
+                get(student_id)
+            ));
+            continue;
+        }
+        if !seen_ids.insert(get(student_id).to_string()) {
+            status.push(format!(
+                "Line {}: duplicate student id {}, skipped\n",
+                line,
+                get(student_id)
+            ));
+            continue;
+        }
+        if !seen_githubs.insert(get(github).to_string()) {
+            status.push(format!(
+                "Line {}: duplicate GitHub {}, skipped\n",
+                line,
+                get(github)
+            ));
+            continue;
+        }
+
+        let mut fields = HashMap::new();
+        for (name, index) in extra.iter() {
+            fields.insert(name.clone(), get(*index).to_string());
+        }
+        students.push(Student {
+            student_id: get(student_id).to_string(),
+            name: get(name).to_string(),
+            github: get(github).to_string(),
+            extra: fields,
+            team: None,
+            blackbox: None,
+            whitebox: None,
+            comment: None,
+            last_commit: None,
+            fetch_failed: false,
+            head: None,
+            graded_commit: None,
+            grader_error: false,
+            rubric: Vec::new(),
+            deduction: 0.0,
+        });
+    }
+
+    (extra_columns, students)
+}
+
+// read teams csv having two columns: team name and GitHub user name or student id of a member
+pub fn read_teams(path: &str, students: &mut [Student], status: &mut Vec<String>) {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            status.push(format!("Failed to open {}: {}\n", path, err));
+            return;
+        }
+    };
+    let mut rdr = csv::ReaderBuilder::new().flexible(true).from_reader(file);
+    let mut teams = HashSet::new();
+    for row in rdr.records() {
+        let record = match row {
+            Ok(record) => record,
+            Err(err) => {
+                status.push(format!("Skipping malformed row: {}\n", err));
+                continue;
+            }
+        };
+        let line = record.position().map(|pos| pos.line()).unwrap_or(0);
+        let team = record.get(0).unwrap_or("").trim();
+        let member = record.get(1).unwrap_or("").trim();
+        if team.is_empty() || member.is_empty() {
+            status.push(format!("Line {}: missing team or member, skipped\n", line));
+            continue;
+        }
+        match students
+            .iter_mut()
+            .find(|stu| stu.github == member || stu.student_id == member)
+        {
+            Some(stu) => {
+                if let Some(old) = &stu.team {
+                    status.push(format!(
+                        "Line {}: {} is already in team {}, skipped\n",
+                        line, member, old
+                    ));
+                    continue;
+                }
+                stu.team = Some(team.to_string());
+                teams.insert(team.to_string());
+            }
+            None => {
+                status.push(format!(
+                    "Line {}: member {} of team {} not found in students\n",
+                    line, member, team
+                ));
+            }
+        }
+    }
+    status.push(format!("Read {} teams from {}\n", teams.len(), path));
+}
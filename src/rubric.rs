@@ -0,0 +1,128 @@
+use crate::formula;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub struct Level {
+    pub points: f64,
+    pub text: String,
+}
+
+pub struct Criterion {
+    // also the column title in results
+    pub name: String,
+    pub max: f64,
+    pub levels: Vec<Level>,
+}
+
+// read rubric toml having [[criteria]] tables of name, max and levels
+pub fn read(path: &str, status: &mut Vec<String>) -> Vec<Criterion> {
+    let mut criteria = Vec::new();
+    let mut settings = config::Config::default();
+    if let Err(err) = settings.merge(config::File::from(Path::new(path))) {
+        status.push(format!("Failed to read {}: {}\n", path, err));
+        return criteria;
+    }
+    let values = match settings.get_array("criteria") {
+        Ok(values) => values,
+        Err(err) => {
+            status.push(format!("Failed to read criteria in {}: {}\n", path, err));
+            return criteria;
+        }
+    };
+    for (position, value) in values.into_iter().enumerate() {
+        // malformed criteria are reported and skipped
+        let table = match value.into_table() {
+            Ok(table) => table,
+            Err(err) => {
+                status.push(format!(
+                    "Skipping criterion {} in {}: {}\n",
+                    position + 1,
+                    path,
+                    err
+                ));
+                continue;
+            }
+        };
+        let name = table.get("name").cloned().map(|value| value.into_str());
+        let max = table.get("max").cloned().map(|value| value.into_float());
+        let (name, max) = match (name, max) {
+            (Some(Ok(name)), Some(Ok(max))) => (name, max),
+            _ => {
+                status.push(format!(
+                    "Skipping criterion {} in {}: name and max are required\n",
+                    position + 1,
+                    path
+                ));
+                continue;
+            }
+        };
+        // criteria are variables of the total formula
+        let variable = formula::variable(&name);
+        if formula::RESERVED.contains(&variable.as_str()) {
+            status.push(format!(
+                "Skipping criterion {} in {}: {} is reserved in total formula\n",
+                position + 1,
+                path,
+                name
+            ));
+            continue;
+        }
+        if let Some(other) = criteria
+            .iter()
+            .find(|c: &&Criterion| formula::variable(&c.name) == variable)
+        {
+            status.push(format!(
+                "Skipping criterion {} in {}: {} is the same variable as {} in total formula\n",
+                position + 1,
+                path,
+                name,
+                other.name
+            ));
+            continue;
+        }
+        let mut levels = Vec::new();
+        if let Some(values) = table.get("levels") {
+            for value in values.clone().into_array().unwrap_or_default().into_iter() {
+                let level = value.into_table().ok().and_then(|level| {
+                    Some(Level {
+                        points: level.get("points")?.clone().into_float().ok()?,
+                        text: level.get("text")?.clone().into_str().ok()?,
+                    })
+                });
+                match level {
+                    Some(level) => levels.push(level),
+                    None => status.push(format!(
+                        "Skipping level of {} in {}: points and text are required\n",
+                        name, path
+                    )),
+                }
+            }
+        }
+        criteria.push(Criterion { name, max, levels });
+    }
+    status.push(format!("Read {} criteria from {}\n", criteria.len(), path));
+    criteria
+}
+
+// scores of criteria in columns of a results row
+pub fn scores(criteria: &[Criterion], fields: &HashMap<String, String>) -> Vec<Option<f64>> {
+    criteria
+        .iter()
+        .map(|criterion| {
+            fields
+                .get(&criterion.name)
+                // header relabeled in config, keyed by lowercase name
+                .or_else(|| fields.get(&criterion.name.to_lowercase()))
+                .and_then(|score| score.parse().ok())
+        })
+        .collect()
+}
+
+// sum of scores, None if no criterion is scored
+pub fn total(scores: &[Option<f64>]) -> Option<f64> {
+    if scores.iter().all(Option::is_none) {
+        None
+    } else {
+        Some(scores.iter().flatten().sum())
+    }
+}
@@ -0,0 +1,96 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// ui state restored on next launch of the same assignment
+pub struct Session {
+    // student id of selected student
+    pub select: Option<String>,
+    pub current: String,
+    pub student_render_start: usize,
+    pub log_scroll_start: usize,
+    pub diff_scroll_start: usize,
+    pub status_scroll_back: usize,
+    pub filter: String,
+    pub search: String,
+    pub sort: String,
+    pub sort_descending: bool,
+    // grade, true for whitebox
+    pub last_grade: Option<(Option<f64>, bool)>,
+    pub rubric_select: usize,
+    pub branch: String,
+    // commit hash of repo HEAD when last graded, by student id
+    pub graded_commits: HashMap<String, String>,
+}
+
+// session file of assignment in workspace, one per TA sharing it
+pub fn path(workspace: &str, prefix: &str, ta: &Option<String>) -> PathBuf {
+    let name = match ta {
+        Some(ta) => format!(".session-{}-{}.json", prefix, ta),
+        None => format!(".session-{}.json", prefix),
+    };
+    Path::new(workspace).join(name)
+}
+
+impl Session {
+    pub fn load<P: AsRef<Path>>(path: P) -> Option<Session> {
+        let text = std::fs::read_to_string(path).ok()?;
+        let value: Value = serde_json::from_str(&text).ok()?;
+        let string = |key: &str| value[key].as_str().unwrap_or_default().to_string();
+        let number = |key: &str| value[key].as_u64().unwrap_or(0) as usize;
+        Some(Session {
+            select: value["select"].as_str().map(String::from),
+            current: string("current"),
+            student_render_start: number("student_render_start"),
+            log_scroll_start: number("log_scroll_start"),
+            diff_scroll_start: number("diff_scroll_start"),
+            status_scroll_back: number("status_scroll_back"),
+            filter: string("filter"),
+            search: string("search"),
+            sort: string("sort"),
+            sort_descending: value["sort_descending"].as_bool().unwrap_or(false),
+            last_grade: value["last_grade"].as_object().map(|last| {
+                (
+                    last.get("grade").and_then(Value::as_f64),
+                    last.get("whitebox")
+                        .and_then(Value::as_bool)
+                        .unwrap_or(false),
+                )
+            }),
+            rubric_select: number("rubric_select"),
+            branch: string("branch"),
+            graded_commits: value["graded_commits"]
+                .as_object()
+                .map(|commits| {
+                    commits
+                        .iter()
+                        .filter_map(|(id, commit)| Some((id.clone(), commit.as_str()?.to_string())))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let value = json!({
+            "select": self.select,
+            "current": self.current,
+            "student_render_start": self.student_render_start,
+            "log_scroll_start": self.log_scroll_start,
+            "diff_scroll_start": self.diff_scroll_start,
+            "status_scroll_back": self.status_scroll_back,
+            "filter": self.filter,
+            "search": self.search,
+            "sort": self.sort,
+            "sort_descending": self.sort_descending,
+            "last_grade": self.last_grade.map(|(grade, whitebox)| json!({
+                "grade": grade,
+                "whitebox": whitebox,
+            })),
+            "rubric_select": self.rubric_select,
+            "branch": self.branch,
+            "graded_commits": self.graded_commits,
+        });
+        std::fs::write(path, serde_json::to_vec_pretty(&value).unwrap())
+    }
+}
@@ -0,0 +1,60 @@
+use std::fs::File;
+
+pub struct Snippet {
+    pub text: String,
+    // points deducted from whitebox grade
+    pub deduction: Option<f64>,
+}
+
+// read snippets csv having two columns: comment and optional points to deduct,
+// with an optional header row
+pub fn read(path: &str, status: &mut Vec<String>) -> Vec<Snippet> {
+    let mut snippets = Vec::new();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            status.push(format!("Failed to open {}: {}\n", path, err));
+            return snippets;
+        }
+    };
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(file);
+    for row in rdr.records() {
+        let record = match row {
+            Ok(record) => record,
+            Err(err) => {
+                status.push(format!("Skipping malformed row: {}\n", err));
+                continue;
+            }
+        };
+        let line = record.position().map(|pos| pos.line()).unwrap_or(0);
+        let text = record.get(0).unwrap_or("").trim();
+        let deduction = record.get(1).unwrap_or("").trim();
+        if text.is_empty() {
+            status.push(format!("Line {}: missing comment, skipped\n", line));
+            continue;
+        }
+        let deduction = if deduction.is_empty() {
+            None
+        } else if let Ok(points) = deduction.parse::<f64>() {
+            Some(points)
+        } else if line == 1 {
+            // header, e.g. comment,deduction
+            continue;
+        } else {
+            status.push(format!(
+                "Line {}: invalid deduction {}, skipped\n",
+                line, deduction
+            ));
+            continue;
+        };
+        snippets.push(Snippet {
+            text: text.to_string(),
+            deduction,
+        });
+    }
+    status.push(format!("Read {} snippets from {}\n", snippets.len(), path));
+    snippets
+}
@@ -0,0 +1,206 @@
+use crate::configs::{Config, Tas};
+use crate::model::Student;
+use crate::roster;
+use clap::ArgMatches;
+use std::collections::HashMap;
+use std::fs::File;
+
+// read csv of student id or GitHub and TA
+fn read(path: &str, students: &[Student], status: &mut Vec<String>) -> HashMap<String, String> {
+    let mut assigned = HashMap::new();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            status.push(format!("Failed to open {}: {}\n", path, err));
+            return assigned;
+        }
+    };
+    let mut rdr = csv::ReaderBuilder::new().flexible(true).from_reader(file);
+    for row in rdr.records() {
+        let record = match row {
+            Ok(record) => record,
+            Err(err) => {
+                status.push(format!("Skipping malformed row: {}\n", err));
+                continue;
+            }
+        };
+        let line = record.position().map(|pos| pos.line()).unwrap_or(0);
+        let student = record.get(0).unwrap_or("").trim();
+        let ta = record.get(1).unwrap_or("").trim();
+        if student.is_empty() || ta.is_empty() {
+            status.push(format!("Line {}: missing student or TA, skipped\n", line));
+            continue;
+        }
+        match students
+            .iter()
+            .find(|stu| stu.student_id == student || stu.github == student)
+        {
+            Some(stu) => {
+                assigned.insert(stu.student_id.clone(), ta.to_string());
+            }
+            None => status.push(format!(
+                "Line {}: student {} of TA {} not found in students\n",
+                line, student, ta
+            )),
+        }
+    }
+    assigned
+}
+
+// TA of each student, members of a team share the TA of its first member
+pub fn assign(tas: &Tas, students: &[Student], status: &mut Vec<String>) -> Vec<Option<String>> {
+    let mut assigned: Vec<Option<String>> = vec![None; students.len()];
+    if let Some(path) = &tas.file {
+        let file = read(path, students, status);
+        for (index, stu) in students.iter().enumerate() {
+            assigned[index] = file.get(&stu.student_id).cloned();
+        }
+    } else if let Some(column) = &tas.column {
+        // sections not mapped in config are dealt round-robin in sorted order
+        let mut values: Vec<&str> = students
+            .iter()
+            .filter_map(|stu| stu.extra.get(column).map(String::as_str))
+            .filter(|value| !tas.sections.contains_key(*value))
+            .collect();
+        values.sort_unstable();
+        values.dedup();
+        let mut sections = tas.sections.clone();
+        if !tas.names.is_empty() {
+            for (index, value) in values.iter().enumerate() {
+                sections.insert(
+                    value.to_string(),
+                    tas.names[index % tas.names.len()].clone(),
+                );
+            }
+        } else if !values.is_empty() {
+            status.push(format!(
+                "No TA names in config to assign {} {} to\n",
+                column,
+                values.join(", ")
+            ));
+        }
+        if students.iter().any(|stu| !stu.extra.contains_key(column)) {
+            status.push(format!("Column {} not found in students csv\n", column));
+        }
+        for (index, stu) in students.iter().enumerate() {
+            assigned[index] = stu
+                .extra
+                .get(column)
+                .and_then(|value| sections.get(value).cloned());
+        }
+    } else if tas.names.is_empty() {
+        status.push("No TA names in config to assign students to\n".to_string());
+        return assigned;
+    } else {
+        // one turn per repo, in roster order
+        let mut repos: Vec<&str> = Vec::new();
+        for (index, stu) in students.iter().enumerate() {
+            let repo = stu.team.as_deref().unwrap_or(&stu.student_id);
+            let turn = match repos.iter().position(|r| *r == repo) {
+                Some(turn) => turn,
+                None => {
+                    repos.push(repo);
+                    repos.len() - 1
+                }
+            };
+            assigned[index] = Some(tas.names[turn % tas.names.len()].clone());
+        }
+    }
+
+    let mut teams: HashMap<&str, Option<String>> = HashMap::new();
+    for (index, stu) in students.iter().enumerate() {
+        if let Some(team) = &stu.team {
+            let ta = teams.entry(team).or_insert_with(|| assigned[index].clone());
+            assigned[index] = ta.clone();
+        }
+    }
+    assigned
+}
+
+// TAs named in config, or in the csv of TAs
+fn names(tas: &Tas) -> Vec<String> {
+    let mut names = tas.names.clone();
+    names.extend(tas.sections.values().cloned());
+    if let Some(path) = &tas.file {
+        if let Ok(mut rdr) = csv::ReaderBuilder::new().flexible(true).from_path(path) {
+            for record in rdr.records().flatten() {
+                names.extend(record.get(1).map(|ta| ta.trim().to_string()));
+            }
+        }
+    }
+    names.retain(|ta| !ta.is_empty());
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+// Err if ta given by --ta is not a configured TA
+pub fn check(tas: &Tas, ta: &str) -> Result<(), String> {
+    let names = names(tas);
+    if names.is_empty() {
+        Err(format!(
+            "No TAs configured in [tas] to grade students of {}",
+            ta
+        ))
+    } else if !names.iter().any(|name| name == ta) {
+        Err(format!(
+            "Unknown TA {}, configured TAs: {}",
+            ta,
+            names.join(", ")
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+// print TA of each student, or save them to a csv to keep the split when roster changes
+pub fn run(config: &Config, args: &ArgMatches) {
+    let mut status = Vec::new();
+    let (_, mut students) = roster::read(config, &mut status);
+    if let Some(teams) = &config.teams {
+        roster::read_teams(teams, &mut students, &mut status);
+    }
+    let assigned = assign(&config.tas, &students, &mut status);
+
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    let mut unassigned = 0;
+    for ta in assigned.iter() {
+        match ta {
+            Some(ta) => match counts.iter_mut().find(|(name, _)| name == ta) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((ta.clone(), 1)),
+            },
+            None => unassigned += 1,
+        }
+    }
+
+    if let Some(output) = args.value_of("output") {
+        let mut wtr = csv::Writer::from_path(output).unwrap();
+        wtr.write_record(["student_id", "ta"]).unwrap();
+        for (stu, ta) in students.iter().zip(assigned.iter()) {
+            if let Some(ta) = ta {
+                wtr.write_record([&stu.student_id, ta]).unwrap();
+            }
+        }
+        wtr.flush().unwrap();
+        status.push(format!("Saved to {}\n", output));
+    } else {
+        for (stu, ta) in students.iter().zip(assigned.iter()) {
+            status.push(format!(
+                "{} {} {}\n",
+                stu.student_id,
+                stu.name,
+                ta.as_deref().unwrap_or("-")
+            ));
+        }
+    }
+    for (ta, count) in counts.iter() {
+        status.push(format!("{}: {} students\n", ta, count));
+    }
+    if unassigned > 0 {
+        status.push(format!("{} students not assigned to any TA\n", unassigned));
+    }
+    for line in status.iter() {
+        print!("{}", line);
+    }
+}
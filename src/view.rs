@@ -1,29 +1,59 @@
-use crate::model::{InputMode, Model, UiWidget};
+use crate::gradebook::Gradebook;
+use crate::keys::Action;
+use crate::model::{Areas, Filter, InputMode, Model, SortKey, TextInput, UiWidget};
+use crate::rubric;
+use chrono::Local;
 use tui::backend::Backend;
 use tui::layout::Constraint::*;
-use tui::layout::{Constraint, Direction, Layout};
-use tui::style::{Color, Style};
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
 use tui::terminal::Frame;
 use tui::{
     text::{Span, Spans},
-    widgets::{Block, Borders, Paragraph, Row, Table, Wrap},
+    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, Wrap},
 };
 use unicode_width::UnicodeWidthStr;
 
+// smallest terminal to render all panels
+const MIN_WIDTH: u16 = 60;
+const MIN_HEIGHT: u16 = 20;
+
+// render a message instead of panels on tiny terminals, returns true if too small
+fn draw_too_small<B: Backend>(f: &mut Frame<B>) -> bool {
+    let size = f.size();
+    if size.width >= MIN_WIDTH && size.height >= MIN_HEIGHT {
+        return false;
+    }
+    f.render_widget(
+        Paragraph::new(format!(
+            "Terminal too small: {}x{}, need at least {}x{}",
+            size.width, size.height, MIN_WIDTH, MIN_HEIGHT
+        ))
+        .wrap(Wrap { trim: true }),
+        size,
+    );
+    true
+}
+
 pub fn draw<B: Backend>(model: &mut Model, f: &mut Frame<B>) {
     let highlighted_style = Style::default().fg(Color::Gray);
     let normal_style = Style::default();
 
+    if draw_too_small(f) {
+        model.areas = Areas::default();
+        return;
+    }
+
     let chunks_bottom = Layout::default()
         .direction(Direction::Vertical)
         .horizontal_margin(1)
-        .constraints([Constraint::Percentage(90), Constraint::Percentage(10)].as_ref())
+        .constraints([Min(0), Length(3), Length(1)].as_ref())
         .split(f.size());
 
     let chunks_virt = Layout::default()
         .direction(Direction::Horizontal)
         .vertical_margin(1)
-        .constraints([Constraint::Percentage(45), Constraint::Percentage(55)].as_ref())
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
         .split(chunks_bottom[0]);
 
     let chunks_left = Layout::default()
@@ -31,75 +61,151 @@ pub fn draw<B: Backend>(model: &mut Model, f: &mut Frame<B>) {
         .constraints([Constraint::Percentage(75), Constraint::Percentage(25)].as_ref())
         .split(chunks_virt[0]);
 
+    model.areas = Areas {
+        student: chunks_left[0],
+        status: chunks_left[1],
+        log: Rect::default(),
+        diff: Rect::default(),
+        rubric: Rect::default(),
+    };
+
     // Students
-    if let Some(select) = model.student_select {
+    let visible = model.visible();
+    if let Some(select) = model
+        .student_select
+        .and_then(|index| visible.iter().position(|i| *i == index))
+    {
         if select < model.student_render_start {
             model.student_render_start = select;
-        } else if select > model.student_render_start + (chunks_left[0].height as usize - 6) {
-            model.student_render_start = select - (chunks_left[0].height as usize - 6);
+        } else if select
+            > model.student_render_start + (chunks_left[0].height as usize).saturating_sub(6)
+        {
+            model.student_render_start =
+                select - (chunks_left[0].height as usize).saturating_sub(6);
         }
     }
     let mut students = Vec::new();
     let highlighted_row_style = Style::default().bg(Color::Gray);
     let mut github_width = 10;
-    for (index, stu) in model
-        .students
-        .iter()
-        .enumerate()
-        .skip(model.student_render_start)
-    {
+    for index in visible.iter().skip(model.student_render_start) {
+        let index = *index;
+        let stu = &model.students[index];
         github_width = std::cmp::max(github_width, stu.github.len());
 
         let blackbox = if let Some(grade) = stu.blackbox {
             grade.to_string()
         } else {
-            format!("N/A")
+            "N/A".to_string()
         };
         let whitebox = if let Some(grade) = stu.whitebox {
             grade.to_string()
         } else {
-            format!("N/A")
+            "N/A".to_string()
+        };
+        let deadline = if model.config.deadline.is_none() || stu.last_commit.is_none() {
+            String::new()
+        } else if stu.late(model.config.deadline) {
+            "Late".to_string()
+        } else {
+            "On time".to_string()
         };
+        let last_commit = if let Some(time) = stu.last_commit {
+            time.with_timezone(&Local).format("%m-%d %H:%M").to_string()
+        } else {
+            String::new()
+        };
+        let mut cells = vec![
+            stu.student_id.clone(),
+            stu.name.clone(),
+            stu.github.clone(),
+            blackbox,
+            whitebox,
+        ];
+        if model.total.is_some() {
+            cells.push(
+                model
+                    .total(stu)
+                    .map_or("N/A".to_string(), |total| format!("{:.1}", total)),
+            );
+        }
+        cells.push(deadline);
+        cells.push(last_commit);
         if Some(index) == model.student_select {
-            students.push(
-                Row::new(vec![
-                    stu.student_id.clone(),
-                    stu.name.clone(),
-                    stu.github.clone(),
-                    blackbox,
-                    whitebox,
-                ])
-                .style(highlighted_row_style),
-            )
+            students.push(Row::new(cells).style(highlighted_row_style))
         } else {
-            students.push(Row::new(vec![
-                stu.student_id.clone(),
-                stu.name.clone(),
-                stu.github.clone(),
-                blackbox,
-                whitebox,
-            ]))
+            students.push(Row::new(cells))
         }
     }
 
+    let mut header = Vec::new();
+    for (title, key) in [
+        ("Student Id", SortKey::StudentId),
+        ("Name", SortKey::Name),
+        ("GitHub", SortKey::GitHub),
+        ("Blackbox", SortKey::Blackbox),
+        ("Whitebox", SortKey::Whitebox),
+        ("Deadline", SortKey::Late),
+        ("Last Commit", SortKey::LastCommit),
+    ]
+    .iter()
+    {
+        if model.sort == *key {
+            header.push(format!(
+                "{}{}",
+                title,
+                if model.sort_descending { "↓" } else { "↑" }
+            ));
+        } else {
+            header.push(title.to_string());
+        }
+    }
+    let mut widths = vec![
+        Length(10),
+        Length(10),
+        Length(github_width as u16),
+        Length(9),
+        Length(9),
+        Length(9),
+        Length(12),
+    ];
+    if model.total.is_some() {
+        header.insert(5, "Total".to_string());
+        widths.insert(5, Length(9));
+    }
+
     f.render_widget(
         Table::new(students)
-            .header(Row::new([
-                "Student Id",
-                "Name",
-                "GitHub",
-                "Blackbox",
-                "Whitebox",
-                "Deadline",
-            ]))
+            .header(Row::new(header))
             .block(
                 Block::default()
                     .title(Span::styled(
-                        if let UiWidget::Student = model.current {
-                            " Students * "
-                        } else {
-                            " Students "
-                        },
+                        format!(
+                            " Students ({} of {} graded){}{}{}{} ",
+                            visible
+                                .iter()
+                                .filter(|index| {
+                                    let stu = &model.students[**index];
+                                    stu.blackbox.is_some() && stu.whitebox.is_some()
+                                })
+                                .count(),
+                            visible.len(),
+                            if let Filter::All = model.filter {
+                                String::new()
+                            } else {
+                                format!(" [{}]", model.filter.name())
+                            },
+                            if model.search.is_empty() {
+                                String::new()
+                            } else {
+                                format!(" /{}", model.search)
+                            },
+                            if model.dirty { " [unsaved]" } else { "" },
+                            if let UiWidget::Student = model.current {
+                                " *"
+                            } else {
+                                ""
+                            }
+                        ),
                         if let UiWidget::Student = model.current {
                             highlighted_style
                         } else {
@@ -113,14 +219,7 @@ pub fn draw<B: Backend>(model: &mut Model, f: &mut Frame<B>) {
                         normal_style
                     }),
             )
-            .widths(&[
-                Length(10),
-                Length(10),
-                Length(github_width as u16),
-                Length(8),
-                Length(8),
-                Length(9),
-            ]),
+            .widths(&widths),
         chunks_left[0],
     );
 
@@ -129,11 +228,12 @@ pub fn draw<B: Backend>(model: &mut Model, f: &mut Frame<B>) {
     for line in model.status.iter() {
         status.push(Spans::from(line.clone()));
     }
-    let status_scroll = if status.len() > chunks_left[1].height as usize - 3 {
-        status.len() - (chunks_left[1].height as usize - 3)
-    } else {
-        0
-    };
+    let status_scroll = status
+        .len()
+        .saturating_sub((chunks_left[1].height as usize).saturating_sub(3));
+    // scrolled back by mouse wheel
+    model.status_scroll_back = std::cmp::min(model.status_scroll_back, status_scroll);
+    let status_scroll = status_scroll - model.status_scroll_back;
     f.render_widget(
         Paragraph::new(status)
             .block(
@@ -161,10 +261,26 @@ pub fn draw<B: Backend>(model: &mut Model, f: &mut Frame<B>) {
         chunks_left[1],
     );
 
-    let chunks_right = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-        .split(chunks_virt[1]);
+    // Rubric between Log and Diff if configured
+    let chunks_right = if model.rubric.is_empty() {
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Percentage(50), Percentage(50), Length(0)].as_ref())
+            .split(chunks_virt[1])
+    } else {
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Percentage(35), Percentage(30), Min(0)].as_ref())
+            .split(chunks_virt[1])
+    };
+    let (chunk_log, chunk_diff, chunk_rubric) = if model.rubric.is_empty() {
+        (chunks_right[0], chunks_right[1], chunks_right[2])
+    } else {
+        (chunks_right[0], chunks_right[2], chunks_right[1])
+    };
+    model.areas.log = chunk_log;
+    model.areas.diff = chunk_diff;
+    model.areas.rubric = chunk_rubric;
 
     // Log
     f.render_widget(
@@ -192,7 +308,7 @@ pub fn draw<B: Backend>(model: &mut Model, f: &mut Frame<B>) {
             )
             .scroll((model.log_scroll_start as u16, 0))
             .wrap(Wrap { trim: true }),
-        chunks_right[0],
+        chunk_log,
     );
 
     // Diff
@@ -221,27 +337,32 @@ pub fn draw<B: Backend>(model: &mut Model, f: &mut Frame<B>) {
             )
             .scroll((model.diff_scroll_start as u16, 0))
             .wrap(Wrap { trim: true }),
-        chunks_right[1],
+        chunk_diff,
     );
 
-    // Bottom
+    if !model.rubric.is_empty() {
+        draw_rubric(model, f, chunk_rubric);
+    }
+
+    // Bottom, line breaks of comment shown inline
     f.render_widget(
-        Paragraph::new(model.bottom_line.as_str()).block(
+        Paragraph::new(model.bottom_line.replace('\n', " ↵ ")).block(
             Block::default()
                 .title(Span::styled(
-                    if let InputMode::Text = model.input_mode {
-                        " Comment * "
-                    } else {
-                        " Comment "
+                    match model.input_mode {
+                        InputMode::Text(TextInput::Comment) => " Comment * ",
+                        InputMode::Text(TextInput::Search) => " Search * ",
+                        InputMode::Text(TextInput::Command) => " Command * ",
+                        InputMode::Normal => " Comment ",
                     },
-                    if let InputMode::Text = model.input_mode {
+                    if let InputMode::Text(_) = model.input_mode {
                         highlighted_style
                     } else {
                         normal_style
                     },
                 ))
                 .borders(Borders::ALL)
-                .border_style(if let InputMode::Text = model.input_mode {
+                .border_style(if let InputMode::Text(_) = model.input_mode {
                     highlighted_style
                 } else {
                     normal_style
@@ -250,10 +371,466 @@ pub fn draw<B: Backend>(model: &mut Model, f: &mut Frame<B>) {
         chunks_bottom[1],
     );
 
-    if let InputMode::Text = model.input_mode {
+    if let InputMode::Text(TextInput::Search) | InputMode::Text(TextInput::Command) =
+        model.input_mode
+    {
         f.set_cursor(
             chunks_bottom[1].x + model.bottom_line.width() as u16 + 1,
             chunks_bottom[1].y + 1,
         )
     }
+
+    // Hints
+    f.render_widget(
+        Paragraph::new(Span::styled(hints(model), highlighted_style)),
+        chunks_bottom[2],
+    );
+
+    if let InputMode::Text(TextInput::Comment) = model.input_mode {
+        draw_comment(model, f);
+        if model.snippet_select.is_some() {
+            draw_snippets(model, f);
+        }
+    }
+    if model.history.is_some() {
+        draw_history(model, f);
+    }
+    if model.show_help {
+        draw_help(model, f);
+    }
+    if model.confirm_quit {
+        draw_confirm_quit(model, f);
+    }
+}
+
+fn draw_history<B: Backend>(model: &mut Model, f: &mut Frame<B>) {
+    let area = centered(80, 60, f.size());
+    let name = model
+        .student_select
+        .map_or(String::new(), |index| model.students[index].name.clone());
+    let lines: Vec<Spans> = model
+        .history
+        .iter()
+        .flatten()
+        .map(|line| Spans::from(line.as_str()))
+        .collect();
+    // keep last page on screen
+    let max_scroll = lines
+        .len()
+        .saturating_sub((area.height as usize).saturating_sub(2));
+    model.history_scroll = std::cmp::min(model.history_scroll, max_scroll);
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .title(format!(" History of {} ", name))
+                    .borders(Borders::ALL),
+            )
+            .scroll((model.history_scroll as u16, 0)),
+        area,
+    );
+}
+
+fn draw_confirm_quit<B: Backend>(model: &Model, f: &mut Frame<B>) {
+    let size = f.size();
+    let mut text = "Save changes before quitting?\ns: save  d: discard  esc: cancel".to_string();
+    let mut height = 4;
+    // save failed, keep asking with the error shown
+    if let Some(error) = &model.quit_error {
+        text = format!("{}\n{}", error, text);
+        height += 1;
+    }
+    let longest = text
+        .lines()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0) as u16;
+    let width = std::cmp::min(std::cmp::max(50, longest + 2), size.width);
+    let area = Rect::new((size.width - width) / 2, size.height / 2 - 2, width, height);
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(text).block(
+            Block::default()
+                .title(" Unsaved changes ")
+                .borders(Borders::ALL),
+        ),
+        area,
+    );
+}
+
+fn draw_comment<B: Backend>(model: &Model, f: &mut Frame<B>) {
+    let area = centered(70, 50, f.size());
+    let name = model
+        .student_select
+        .map_or(String::new(), |index| model.students[index].name.clone());
+    // gone when the snippet text is deleted again
+    let deduction = model.snippet_deduction();
+    // keep cursor inside the popup
+    let (line, column) = model.editor.cursor_position();
+    let scroll_y = line.saturating_sub((area.height as usize).saturating_sub(3));
+    let scroll_x = column.saturating_sub((area.width as usize).saturating_sub(3));
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(model.editor.text.as_str())
+            .block(
+                Block::default()
+                    .title(if deduction != 0.0 {
+                        format!(" Comment for {} (-{}) * ", name, deduction)
+                    } else {
+                        format!(" Comment for {} * ", name)
+                    })
+                    .borders(Borders::ALL),
+            )
+            .scroll((scroll_y as u16, scroll_x as u16)),
+        area,
+    );
+    f.set_cursor(
+        area.x + (column - scroll_x) as u16 + 1,
+        area.y + (line - scroll_y) as u16 + 1,
+    );
+}
+
+fn draw_rubric<B: Backend>(model: &Model, f: &mut Frame<B>, area: Rect) {
+    let highlighted_style = Style::default().fg(Color::Gray);
+    let highlighted_row_style = Style::default().bg(Color::Gray);
+    let focused = matches!(model.current, UiWidget::Rubric);
+    let scores = model
+        .student_select
+        .map(|index| model.students[index].rubric.clone())
+        .unwrap_or_default();
+
+    let width = model
+        .rubric
+        .iter()
+        .map(|criterion| criterion.name.width())
+        .max()
+        .unwrap_or(0);
+    let mut lines = Vec::new();
+    for (index, criterion) in model.rubric.iter().enumerate() {
+        let score = scores
+            .get(index)
+            .cloned()
+            .flatten()
+            .map_or("N/A".to_string(), |score| score.to_string());
+        lines.push(Spans::from(Span::styled(
+            format!(
+                "{}{}  {} / {}",
+                criterion.name,
+                " ".repeat(width - criterion.name.width()),
+                score,
+                criterion.max
+            ),
+            if focused && index == model.rubric_select {
+                highlighted_row_style
+            } else {
+                Style::default()
+            },
+        )));
+    }
+    // levels of selected criterion
+    if let Some(criterion) = model.rubric.get(model.rubric_select) {
+        if !criterion.levels.is_empty() {
+            lines.push(Spans::from(""));
+        }
+        for level in criterion.levels.iter() {
+            lines.push(Spans::from(format!("  {}: {}", level.points, level.text)));
+        }
+    }
+
+    let total = rubric::total(&scores).unwrap_or(0.0);
+    let max: f64 = model.rubric.iter().map(|criterion| criterion.max).sum();
+    // whitebox differs from the sum after snippet deductions
+    let deduction = model
+        .student_select
+        .map(|index| model.students[index].deduction)
+        .filter(|deduction| *deduction != 0.0)
+        .map_or(String::new(), |deduction| {
+            format!(", -{} by snippets", deduction)
+        });
+    let title = format!(
+        " Rubric ({} / {}{}){} ",
+        total,
+        max,
+        deduction,
+        if focused { " *" } else { "" }
+    );
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .title(Span::styled(
+                    title,
+                    if focused {
+                        highlighted_style
+                    } else {
+                        Style::default()
+                    },
+                ))
+                .borders(Borders::ALL)
+                .border_style(if focused {
+                    highlighted_style
+                } else {
+                    Style::default()
+                }),
+        ),
+        area,
+    );
+}
+
+fn draw_snippets<B: Backend>(model: &Model, f: &mut Frame<B>) {
+    let highlighted_row_style = Style::default().bg(Color::Gray);
+    let area = centered(60, 40, f.size());
+    let select = model.snippet_select.unwrap_or(0);
+    let mut lines = Vec::new();
+    for (index, snippet) in model.snippets.iter().enumerate() {
+        let line = match snippet.deduction {
+            Some(points) => format!("{}. {} (-{})", index + 1, snippet.text, points),
+            None => format!("{}. {}", index + 1, snippet.text),
+        };
+        lines.push(Spans::from(Span::styled(
+            line,
+            if index == select {
+                highlighted_row_style
+            } else {
+                Style::default()
+            },
+        )));
+    }
+    // keep selection on screen
+    let scroll = select.saturating_sub((area.height as usize).saturating_sub(3));
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(Block::default().title(" Snippets ").borders(Borders::ALL))
+            .scroll((scroll as u16, 0)),
+        area,
+    );
+}
+
+// keys of common actions in focused panel or current input
+fn hints(model: &Model) -> String {
+    let keys = &model.config.keys;
+    if model.confirm_quit {
+        return "s: save and quit  d: discard and quit  esc: cancel".to_string();
+    }
+    if model.history.is_some() {
+        return format!(
+            "esc: close history  {}",
+            keys.hints(&[(Action::Down, "scroll")])
+        );
+    }
+    if model.show_help {
+        return format!(
+            "esc: close help  {}",
+            keys.hints(&[(Action::Down, "scroll")])
+        );
+    }
+    match model.input_mode {
+        InputMode::Text(TextInput::Comment) if model.snippet_select.is_some() => {
+            return "j/k: move  enter: append  1-9: append numbered  esc: close".to_string()
+        }
+        InputMode::Text(TextInput::Comment) => {
+            return "enter/ctrl-s: save  tab: snippets  esc: cancel  alt-enter/ctrl-j: new line  ctrl-w: delete word  ctrl-e: open $EDITOR"
+                .to_string()
+        }
+        InputMode::Text(TextInput::Search) => {
+            return "enter: keep search  esc: cancel search".to_string()
+        }
+        InputMode::Text(TextInput::Command) => {
+            return "tab: complete  enter: run  esc: cancel  :help for commands".to_string()
+        }
+        InputMode::Normal => {}
+    }
+    let actions: &[(Action, &str)] = match model.current {
+        UiWidget::Student => &[
+            (Action::Down, "next"),
+            (Action::Up, "previous"),
+            (Action::Fetch, "fetch"),
+            (Action::Grade, "grade"),
+            (Action::Blackbox, "[num] blackbox"),
+            (Action::Whitebox, "[num] whitebox"),
+            (Action::Comment, "comment"),
+            (Action::Search, "search"),
+            (Action::Command, "command"),
+            (Action::Save, "save"),
+            (Action::Help, "help"),
+        ],
+        UiWidget::Rubric => &[
+            (Action::Down, "next criterion"),
+            (Action::Up, "previous criterion"),
+            (Action::Whitebox, "[num] score"),
+            (Action::FocusLeft, "students"),
+            (Action::Save, "save"),
+            (Action::Help, "help"),
+        ],
+        UiWidget::Status | UiWidget::Log | UiWidget::Diff => &[
+            (Action::Down, "scroll down"),
+            (Action::Up, "scroll up"),
+            (Action::FocusLeft, "students"),
+            (Action::Save, "save"),
+            (Action::Help, "help"),
+        ],
+    };
+    keys.hints(actions)
+}
+
+// centered rect with percentage of width and height of area
+fn centered(width: u16, height: u16, area: Rect) -> Rect {
+    let vert = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Percentage((100 - height) / 2),
+                Percentage(height),
+                Percentage((100 - height) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Percentage((100 - width) / 2),
+                Percentage(width),
+                Percentage((100 - width) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(vert[1])[1]
+}
+
+fn draw_help<B: Backend>(model: &mut Model, f: &mut Frame<B>) {
+    let area = centered(70, 80, f.size());
+    let mut lines = Vec::new();
+    for (group, bindings) in model.config.keys.help() {
+        if bindings.is_empty() {
+            continue;
+        }
+        if !lines.is_empty() {
+            lines.push(Spans::from(""));
+        }
+        lines.push(Spans::from(Span::styled(
+            group,
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        let width = bindings
+            .iter()
+            .map(|(keys, _)| keys.width())
+            .max()
+            .unwrap_or(0);
+        for (keys, description) in bindings {
+            lines.push(Spans::from(format!(
+                "  {:>width$}  {}",
+                keys,
+                description,
+                width = width
+            )));
+        }
+    }
+    // keep last page on screen
+    let max_scroll = lines
+        .len()
+        .saturating_sub((area.height as usize).saturating_sub(2));
+    model.help_scroll = std::cmp::min(model.help_scroll, max_scroll);
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(Block::default().title(" Help ").borders(Borders::ALL))
+            .scroll((model.help_scroll as u16, 0)),
+        area,
+    );
+}
+
+pub fn draw_gradebook<B: Backend>(gradebook: &mut Gradebook, f: &mut Frame<B>) {
+    if draw_too_small(f) {
+        return;
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Percentage(80), Constraint::Percentage(20)].as_ref())
+        .split(f.size());
+
+    // Gradebook
+    let visible = (chunks[0].height as usize).saturating_sub(4);
+    if let Some(select) = gradebook.select {
+        if select < gradebook.render_start {
+            gradebook.render_start = select;
+        } else if select > gradebook.render_start + visible {
+            gradebook.render_start = select - visible;
+        }
+    }
+    let highlighted_row_style = Style::default().bg(Color::Gray);
+    let dropped_style = Style::default().fg(Color::DarkGray);
+    let mut rows = Vec::new();
+    for (index, entry) in gradebook
+        .entries
+        .iter()
+        .enumerate()
+        .skip(gradebook.render_start)
+    {
+        let mut cells = vec![
+            Cell::from(entry.student_id.clone()),
+            Cell::from(entry.name.clone()),
+        ];
+        for (score, dropped) in entry.scores.iter().zip(entry.dropped.iter()) {
+            let text = if let Some(score) = score {
+                format!("{:.1}", score)
+            } else {
+                "N/A".to_string()
+            };
+            if *dropped {
+                cells.push(Cell::from(text).style(dropped_style));
+            } else {
+                cells.push(Cell::from(text));
+            }
+        }
+        cells.push(Cell::from(format!("{:.2}", entry.total)));
+        if Some(index) == gradebook.select {
+            rows.push(Row::new(cells).style(highlighted_row_style));
+        } else {
+            rows.push(Row::new(cells));
+        }
+    }
+
+    let mut header = vec!["Student Id".to_string(), "Name".to_string()];
+    let mut widths = vec![Length(10), Length(10)];
+    for assignment in gradebook.config.assignments.iter() {
+        header.push(assignment.name.clone());
+        widths.push(Length(std::cmp::max(assignment.name.width(), 6) as u16));
+    }
+    header.push("Total".to_string());
+    widths.push(Length(8));
+
+    f.render_widget(
+        Table::new(rows)
+            .header(Row::new(header))
+            .block(
+                Block::default()
+                    .title(Span::styled(" Gradebook ", Style::default()))
+                    .borders(Borders::ALL),
+            )
+            .widths(&widths),
+        chunks[0],
+    );
+
+    // Status
+    let mut status = Vec::new();
+    for line in gradebook.status.iter() {
+        status.push(Spans::from(line.clone()));
+    }
+    let height = (chunks[1].height as usize).saturating_sub(2);
+    let status_scroll = status.len().saturating_sub(height);
+    f.render_widget(
+        Paragraph::new(status)
+            .block(Block::default().title(" Status ").borders(Borders::ALL))
+            .scroll((status_scroll as u16, 0)),
+        chunks[1],
+    );
 }